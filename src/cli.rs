@@ -1,4 +1,7 @@
-use clap::{Args, Parser, Subcommand};
+use crate::{config::Backend, db::transfer::FileFormat, report::Format};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(about = "this program will always remain manjaliof")]
@@ -8,6 +11,19 @@ pub struct Cli {
 
     #[arg(long, default_value_t = false)]
     pub skip_post_script: bool,
+
+    #[arg(
+        long,
+        help = "config file to use instead of config.toml in data folder"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "where data is stored [default: from config or sqlite]"
+    )]
+    pub backend: Option<Backend>,
 }
 
 #[derive(Subcommand, PartialEq)]
@@ -24,9 +40,18 @@ pub enum Commands {
     #[command(about = "edit client datas")]
     Edit(EditArgs),
 
-    #[command(about = "remove client")]
+    #[command(about = "edit every client that matches the target, e.g. to compensate a downtime")]
+    BulkEdit(BulkEditArgs),
+
+    #[command(about = "remove client, it's archived until purged")]
     Remove(RemoveArgs),
 
+    #[command(about = "restore removed client")]
+    Restore(RestoreArgs),
+
+    #[command(about = "permanently delete clients that are archived long time ago")]
+    Purge(PurgeArgs),
+
     #[command(about = "show all clients")]
     List(ListArgs),
 
@@ -37,7 +62,52 @@ pub enum Commands {
     SetInfo(SetInfoArgs),
 
     #[command(about = "remove expired clients that are expired long time ago")]
-    Cleanup,
+    Cleanup(CleanupArgs),
+
+    #[command(about = "show every payment of client")]
+    History(HistoryArgs),
+
+    #[command(about = "show payments of all clients in date order")]
+    Ledger(LedgerArgs),
+
+    #[command(about = "show aggregated reports of payments")]
+    Report(ReportArgs),
+
+    #[command(about = "manage sellers")]
+    Seller(SellerArgs),
+
+    #[command(about = "manage pricing plans")]
+    Plan(PlanArgs),
+
+    #[command(about = "show log of commands that changed database")]
+    Audit(AuditArgs),
+
+    #[command(about = "reverts the most recent operation, or the given one")]
+    Undo(UndoArgs),
+
+    #[command(about = "manage tags of clients")]
+    Tag(TagArgs),
+
+    #[command(about = "notify about clients that are about to expire, meant to run by cron")]
+    Notify(NotifyArgs),
+
+    #[command(about = "manage post hooks that failed and are queued")]
+    Hooks(HooksArgs),
+
+    #[command(about = "write every client, seller and plan to a file")]
+    Export(ExportArgs),
+
+    #[command(about = "read clients, sellers and plans from an exported file or another database")]
+    Import(ImportArgs),
+
+    #[command(about = "manage database")]
+    Db(DbArgs),
+
+    #[command(about = "manage backups of database")]
+    Backup(BackupArgs),
+
+    #[command(about = "inspect configuration")]
+    Config(ConfigArgs),
 
     #[command(about = "show message and sha256 of latest commit that is built from")]
     Version,
@@ -46,6 +116,24 @@ pub enum Commands {
     GenerateBashCompletion,
 }
 
+impl Commands {
+    // commands whose changes are previewed and confirmed as a whole
+    pub fn confirm_args(&self) -> Option<&ConfirmArgs> {
+        match self {
+            Commands::RenewAll(args) => Some(&args.target.confirm),
+            Commands::BulkEdit(args) => Some(&args.target.confirm),
+            Commands::Remove(args) => Some(&args.target.confirm),
+            Commands::SetInfo(args) => Some(&args.target.confirm),
+            Commands::Cleanup(args) => Some(&args.confirm),
+            _ => None,
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.confirm_args().is_some_and(|args| args.dry_run)
+    }
+}
+
 #[derive(Args, PartialEq)]
 pub struct AddArgs {
     #[arg(long)]
@@ -60,16 +148,148 @@ pub struct AddArgs {
     #[arg(long)]
     pub money: Option<u32>,
 
+    #[arg(long, help = "fills days and money that are not given from plan")]
+    pub plan: Option<String>,
+
     #[arg(long)]
     pub info: Option<String>,
+
+    #[command(flatten)]
+    pub profile: ProfileArgs,
 }
 
 pub type RenewArgs = AddArgs;
 
+// empty value clears the field
+#[derive(Args, PartialEq)]
+pub struct ProfileArgs {
+    #[arg(long, help = "phone number or any other way to reach client")]
+    pub contact: Option<String>,
+
+    #[arg(long)]
+    pub telegram: Option<String>,
+
+    #[arg(long)]
+    pub email: Option<String>,
+
+    #[arg(long, help = "free-form notes, not limited like info")]
+    pub notes: Option<String>,
+
+    #[arg(long, help = "who introduced the client")]
+    pub referred_by: Option<String>,
+}
+
+impl ProfileArgs {
+    pub fn is_empty(&self) -> bool {
+        self.contact.is_none()
+            && self.telegram.is_none()
+            && self.email.is_none()
+            && self.notes.is_none()
+            && self.referred_by.is_none()
+    }
+}
+
 #[derive(Args, PartialEq)]
 pub struct RenewAllArgs {
     #[arg(long)]
     pub days: Option<u32>,
+
+    #[arg(
+        long,
+        help = "why days are given, kept in history [default: renew-all]"
+    )]
+    pub reason: Option<String>,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
+}
+
+// selects the clients of a bulk operation, only one of them can be given
+#[derive(Args, PartialEq)]
+pub struct TargetArgs {
+    #[arg(long, value_delimiter = ',', help = "comma separated names of clients")]
+    pub names: Vec<String>,
+
+    #[arg(long, help = "clients whose name matches glob pattern, e.g. 'foo-*'")]
+    pub name_glob: Option<String>,
+
+    #[arg(long, help = "clients whose last payment is made by seller")]
+    pub seller: Option<String>,
+
+    #[arg(long, value_parser = parse_days, help = "clients expiring within days, e.g. 7d")]
+    pub expiring_within: Option<u32>,
+
+    #[arg(long, help = "clients with this tag")]
+    pub tag: Option<String>,
+
+    #[arg(long, help = "clients whose info contains text")]
+    pub info_contains: Option<String>,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+}
+
+// bulk operations preview how every client changes before it's saved
+#[derive(Args, PartialEq)]
+pub struct ConfirmArgs {
+    #[arg(
+        long,
+        short,
+        default_value_t = false,
+        help = "don't ask for confirmation"
+    )]
+    pub yes: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "only show how clients would change, nothing is saved"
+    )]
+    pub dry_run: bool,
+}
+
+impl TargetArgs {
+    pub fn given_flags(&self) -> Vec<&'static str> {
+        [
+            ("--names", !self.names.is_empty()),
+            ("--name-glob", self.name_glob.is_some()),
+            ("--seller", self.seller.is_some()),
+            ("--expiring-within", self.expiring_within.is_some()),
+            ("--tag", self.tag.is_some()),
+            ("--info-contains", self.info_contains.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(flag, is_given)| is_given.then_some(flag))
+        .collect()
+    }
+}
+
+#[derive(Args, PartialEq)]
+pub struct BulkEditArgs {
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    #[arg(
+        long,
+        allow_hyphen_values = true,
+        help = "moves expire time by days without a payment, negative to take days back"
+    )]
+    pub days: Option<i64>,
+
+    #[arg(
+        long,
+        help = "why days are moved, kept in history [default: bulk-edit]"
+    )]
+    pub reason: Option<String>,
+
+    #[arg(long)]
+    pub info: Option<String>,
+
+    #[arg(long, help = "adds tags, can be given more than once")]
+    pub add_tag: Vec<String>,
+
+    #[arg(long, help = "removes tags, can be given more than once")]
+    pub remove_tag: Vec<String>,
 }
 
 pub type EditArgs = AddArgs;
@@ -78,12 +298,99 @@ pub type EditArgs = AddArgs;
 pub struct RemoveArgs {
     #[arg(long)]
     pub name: Option<String>,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
 }
 
 #[derive(Args, PartialEq)]
-pub struct ListArgs {
+pub struct RestoreArgs {
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct CleanupArgs {
+    #[arg(long, value_parser = parse_days, help = "days after expiry, e.g. 10d [default: from config or 5d]")]
+    pub grace: Option<u32>,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "skip clients whose post script fails instead of aborting"
+    )]
+    pub keep_going: bool,
+
+    #[arg(long, help = "only clean up clients with this tag")]
+    pub tag: Option<String>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct PurgeArgs {
+    #[arg(long, value_parser = parse_days, help = "purge clients archived before this many days, e.g. 365d")]
+    pub older_than: u32,
+}
+
+#[derive(Args, PartialEq)]
+pub struct OutputArgs {
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
+
     #[arg(long, default_value_t = false)]
     pub trim_whitespace: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum ListSort {
+    Name,
+    Expire,
+    LastPayment,
+    TotalPaid,
+}
+
+#[derive(Args, PartialEq)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[arg(long, default_value_t = false, help = "show removed clients instead")]
+    pub archived: bool,
+
+    #[arg(long, default_value_t = false, help = "only show expired clients")]
+    pub expired: bool,
+
+    #[arg(long, value_parser = parse_days, help = "only show clients expiring within days, e.g. 7d")]
+    pub expiring_within: Option<u32>,
+
+    #[arg(long, help = "only show clients whose last payment is made by seller")]
+    pub seller: Option<String>,
+
+    #[arg(long)]
+    pub info_contains: Option<String>,
+
+    #[arg(
+        long,
+        help = "only show clients with text in name, info or profile, ignoring case"
+    )]
+    pub search: Option<String>,
+
+    #[arg(long)]
+    pub tag: Option<String>,
+
+    #[arg(
+        long,
+        help = "only show clients whose name matches glob pattern, e.g. 'foo-*'"
+    )]
+    pub name_glob: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = ListSort::Expire)]
+    pub sort: ListSort,
+
+    #[arg(long)]
+    pub limit: Option<usize>,
 
     #[arg(short, long, default_value_t = false)]
     pub verbose: bool,
@@ -109,6 +416,375 @@ pub struct SetInfoArgs {
     #[arg(long)]
     pub name: Option<String>,
 
+    #[command(flatten)]
+    pub target: TargetArgs,
+
     #[arg(long)]
     pub info: Option<String>,
 }
+
+#[derive(Args, PartialEq)]
+pub struct HistoryArgs {
+    #[arg(long)]
+    pub name: Option<String>,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Args, PartialEq)]
+pub struct LedgerArgs {
+    #[arg(
+        long,
+        help = "only show payments made on or after this date (YYYY-MM-DD)"
+    )]
+    pub since: Option<NaiveDate>,
+
+    #[arg(
+        long,
+        help = "only show payments made on or before this date (YYYY-MM-DD)"
+    )]
+    pub until: Option<NaiveDate>,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Args, PartialEq)]
+pub struct ReportArgs {
+    #[command(subcommand)]
+    pub command: ReportCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum ReportCommands {
+    #[command(about = "show revenue of each seller grouped by period")]
+    Revenue(RevenueArgs),
+
+    #[command(about = "compute who owes whom for payments of a month")]
+    Settle(SettleArgs),
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum RevenuePeriod {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum RevenueGroupBy {
+    Seller,
+    Plan,
+}
+
+#[derive(Args, PartialEq)]
+pub struct RevenueArgs {
+    #[arg(long, value_enum, default_value_t = RevenuePeriod::Month)]
+    pub by: RevenuePeriod,
+
+    #[arg(long, value_enum, default_value_t = RevenueGroupBy::Seller)]
+    pub group: RevenueGroupBy,
+
+    #[arg(
+        long,
+        help = "only count payments made on or after this date (YYYY-MM-DD)"
+    )]
+    pub since: Option<NaiveDate>,
+
+    #[arg(
+        long,
+        help = "only count payments made on or before this date (YYYY-MM-DD)"
+    )]
+    pub until: Option<NaiveDate>,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Args, PartialEq)]
+pub struct SettleArgs {
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub between: Vec<String>,
+
+    #[arg(long, value_parser = parse_month, help = "month to settle (YYYY-MM)")]
+    pub month: NaiveDate,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "share of each seller in order of --between, equal shares by default"
+    )]
+    pub split: Vec<u32>,
+
+    #[arg(long, default_value_t = false)]
+    pub trim_whitespace: bool,
+}
+
+pub fn parse_days(days: &str) -> Result<u32, String> {
+    days.strip_suffix('d')
+        .unwrap_or(days)
+        .parse()
+        .map_err(|_| "days must be a number optionally followed by 'd', e.g. 7d".to_string())
+}
+
+fn parse_month(month: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+        .map_err(|_| "month must be in YYYY-MM format".to_string())
+}
+
+#[derive(Args, PartialEq)]
+pub struct SellerArgs {
+    #[command(subcommand)]
+    pub command: SellerCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum SellerCommands {
+    #[command(about = "adds new seller")]
+    Add(SellerNameArgs),
+
+    #[command(about = "remove seller, payments made by seller are kept")]
+    Remove(SellerNameArgs),
+
+    #[command(about = "show all sellers")]
+    List,
+
+    #[command(about = "rename seller and the payments made by seller")]
+    Rename(RenameArgs),
+}
+
+#[derive(Args, PartialEq)]
+pub struct SellerNameArgs {
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct PlanArgs {
+    #[command(subcommand)]
+    pub command: PlanCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum PlanCommands {
+    #[command(about = "adds new plan")]
+    Add(PlanAddArgs),
+
+    #[command(about = "remove plan, payments made under plan are kept")]
+    Remove(PlanNameArgs),
+
+    #[command(about = "show all plans")]
+    List,
+}
+
+#[derive(Args, PartialEq)]
+pub struct PlanAddArgs {
+    #[arg(long)]
+    pub name: Option<String>,
+
+    #[arg(long)]
+    pub days: Option<u32>,
+
+    #[arg(long)]
+    pub money: Option<u32>,
+}
+
+pub type PlanNameArgs = SellerNameArgs;
+
+#[derive(Args, PartialEq)]
+pub struct AuditArgs {
+    #[arg(long, help = "only show operations that changed this client")]
+    pub name: Option<String>,
+
+    #[arg(long)]
+    pub operation: Option<String>,
+
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        help = "show what changed in each client"
+    )]
+    pub verbose: bool,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Args, PartialEq)]
+pub struct UndoArgs {
+    #[arg(long, help = "id of operation from audit log")]
+    pub id: Option<u64>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct TagArgs {
+    #[command(subcommand)]
+    pub command: TagCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum TagCommands {
+    #[command(about = "adds tags to client")]
+    Add(TagEditArgs),
+
+    #[command(about = "removes tags from client")]
+    Remove(TagEditArgs),
+
+    #[command(about = "show all tags with number of clients")]
+    List,
+}
+
+#[derive(Args, PartialEq)]
+pub struct TagEditArgs {
+    #[arg(long)]
+    pub name: Option<String>,
+
+    #[arg(required = true)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct ExportArgs {
+    #[arg(long, value_enum, help = "[default: from extension of --out]")]
+    pub format: Option<FileFormat>,
+
+    #[arg(long)]
+    pub out: PathBuf,
+}
+
+#[derive(Args, PartialEq)]
+pub struct ImportArgs {
+    #[arg(long)]
+    pub from: PathBuf,
+
+    #[arg(long, value_enum, help = "[default: from extension of --from]")]
+    pub format: Option<FileFormat>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "keep existing clients and add payments, adjustments and tags they don't have"
+    )]
+    pub merge: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "overwrite existing clients with imported ones"
+    )]
+    pub replace: bool,
+}
+
+#[derive(Args, PartialEq)]
+pub struct NotifyArgs {
+    #[arg(
+        long,
+        help = "show notifications that would be sent without sending them"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Args, PartialEq)]
+pub struct HooksArgs {
+    #[command(subcommand)]
+    pub command: HooksCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum HooksCommands {
+    #[command(about = "show queued hooks")]
+    List,
+
+    #[command(about = "run queued hooks again, the ones that succeed are removed from queue")]
+    Retry(HooksRetryArgs),
+
+    #[command(about = "show output of recent hook runs")]
+    Log(HooksLogArgs),
+}
+
+#[derive(Args, PartialEq)]
+pub struct HooksRetryArgs {
+    #[arg(
+        long,
+        help = "id of queued hook, all of them are retried when not given"
+    )]
+    pub id: Option<u64>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct HooksLogArgs {
+    #[arg(long, default_value_t = 5, help = "how many of the most recent runs")]
+    pub limit: usize,
+
+    #[arg(long, help = "only show runs of this script")]
+    pub script: Option<String>,
+}
+
+#[derive(Args, PartialEq)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum ConfigCommands {
+    #[command(about = "show configuration in effect, including defaults")]
+    Show,
+
+    #[command(about = "check configuration file for errors")]
+    Validate,
+}
+
+#[derive(Args, PartialEq)]
+pub struct DbArgs {
+    #[command(subcommand)]
+    pub command: DbCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum DbCommands {
+    #[command(about = "apply pending schema migrations")]
+    Migrate(MigrateArgs),
+}
+
+#[derive(Args, PartialEq)]
+pub struct MigrateArgs {
+    #[arg(long, default_value_t = false)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, PartialEq)]
+pub struct BackupArgs {
+    #[command(subcommand)]
+    pub command: BackupCommands,
+}
+
+#[derive(Subcommand, PartialEq)]
+pub enum BackupCommands {
+    #[command(about = "list backups from oldest to newest")]
+    List,
+
+    #[command(about = "back up database now")]
+    Create,
+
+    #[command(about = "replace database with a backup, current database is backed up first")]
+    Restore(BackupRestoreArgs),
+}
+
+#[derive(Args, PartialEq)]
+pub struct BackupRestoreArgs {
+    pub id: String,
+
+    #[arg(
+        long,
+        short,
+        default_value_t = false,
+        help = "don't ask for confirmation"
+    )]
+    pub yes: bool,
+}
@@ -0,0 +1,202 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+
+// every field has a default, so a missing file or section behaves like before config existed
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub defaults: DefaultsConfig,
+    pub display: DisplayConfig,
+    pub limits: LimitsConfig,
+    pub cleanup: CleanupConfig,
+    pub hooks: HooksConfig,
+    pub notify: NotifyConfig,
+    pub database: DatabaseConfig,
+    pub backup: BackupConfig,
+}
+
+// values suggested when they're not passed as arguments
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsConfig {
+    pub days: u32,
+    pub money: u32,
+}
+
+impl Default for DefaultsConfig {
+    fn default() -> Self {
+        DefaultsConfig {
+            days: 30,
+            money: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    // clients expiring within this many days are highlighted
+    pub warn_days: u32,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig { warn_days: 15 }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_name_length: usize,
+    pub max_info_length: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_name_length: 35,
+            max_info_length: 64,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanupConfig {
+    // days after expiry before a client is cleaned up
+    pub grace_days: u32,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        CleanupConfig { grace_days: 5 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    // seconds before a hook is killed, 0 lets it run forever
+    pub timeout_secs: u64,
+    // extra attempts of a failed post hook, delay doubles after every attempt
+    pub retries: u32,
+    pub retry_delay_secs: u64,
+    // commit the operation when a post hook fails and keep the hook to run by `hooks retry`
+    pub queue_failed: bool,
+    // timeout of each event that overrides `timeout_secs`
+    pub timeouts: BTreeMap<String, u64>,
+    // newest logs that are kept, older ones are removed when a hook runs
+    pub keep_logs: usize,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        HooksConfig {
+            timeout_secs: 0,
+            retries: 0,
+            retry_delay_secs: 1,
+            queue_failed: false,
+            timeouts: BTreeMap::new(),
+            keep_logs: 500,
+        }
+    }
+}
+
+impl HooksConfig {
+    pub fn timeout(&self, event: &str) -> u64 {
+        *self.timeouts.get(event).unwrap_or(&self.timeout_secs)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    // days left before expiry that a client is notified at, 0 is for expired clients
+    pub thresholds: Vec<u32>,
+    // used when there is no notify hook, relative paths are inside data folder
+    pub spool_file: String,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            thresholds: vec![7, 3, 1, 0],
+            spool_file: "notifications".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub backend: Backend,
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Sqlite,
+    // a human-diffable file, e.g. to keep data in git
+    Json,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    pub schedule: BackupSchedule,
+    // newest backups that are kept, older ones are removed after every backup
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            schedule: BackupSchedule::Command,
+            keep: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupSchedule {
+    // before every command that changes database
+    Command,
+    // before the first command that changes database each day
+    Daily,
+    Never,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        if !path.is_file() {
+            return Ok(Config::default());
+        }
+
+        let error_msg = |e: String| format!("invalid config '{}': {e}", path.to_str().unwrap());
+        let content = fs::read_to_string(path).map_err(|e| error_msg(e.to_string()))?;
+        let config: Config = toml::from_str(&content).map_err(|e| error_msg(e.to_string()))?;
+        config.validate().map_err(error_msg)?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.defaults.days == 0 {
+            return Err("defaults.days must be more than 0".to_string());
+        }
+        if self.limits.max_name_length == 0 || self.limits.max_info_length == 0 {
+            return Err("limits must be more than 0".to_string());
+        }
+        if self.backup.keep == 0 {
+            return Err("backup.keep must be more than 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap()
+    }
+}
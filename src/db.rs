@@ -1,18 +1,96 @@
 mod datetime_serializer;
 
+pub mod audit;
+pub mod backup;
 pub mod jsondb;
+pub mod migrations;
 pub mod sqlitedb;
+pub mod transfer;
 
+use audit::{AuditEntry, AuditFilter, ClientChange};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::BTreeMap};
 
-#[derive(Serialize, Deserialize, Clone)]
+pub const INITIAL_SELLERS: [&str; 2] = ["arian", "pouya"];
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Payment {
     pub seller: String,
     pub money: u32,
 
     #[serde(with = "datetime_serializer")]
     pub date: DateTime<Utc>,
+
+    // payments made before days were recorded don't have it
+    #[serde(default)]
+    pub days: Option<u32>,
+
+    #[serde(default)]
+    pub plan: Option<String>,
+}
+
+// days given or taken back without a payment, e.g. to compensate a downtime
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct Adjustment {
+    #[serde(with = "datetime_serializer")]
+    pub date: DateTime<Utc>,
+
+    pub days: i64,
+    pub reason: String,
+    // user who made it, only advisory like user of audit entries
+    pub operator: String,
+}
+
+impl Adjustment {
+    pub fn new(days: i64, reason: &str) -> Adjustment {
+        Adjustment {
+            date: Utc::now(),
+            days,
+            reason: reason.to_string(),
+            operator: audit::current_user(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Plan {
+    pub name: String,
+    pub days: u32,
+    pub money: u32,
+}
+
+// expiry notification that is sent, a renewed client gets notified again for its new expire time
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Notification {
+    pub client_name: String,
+    // days left when it's sent, 0 means expired
+    pub threshold: u32,
+
+    #[serde(with = "datetime_serializer")]
+    pub expire_time: DateTime<Utc>,
+
+    #[serde(with = "datetime_serializer")]
+    pub sent_at: DateTime<Utc>,
+}
+
+// post hook that failed after its operation was committed, kept so it can be retried
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PendingHook {
+    pub id: u64,
+
+    #[serde(with = "datetime_serializer")]
+    pub date: DateTime<Utc>,
+
+    pub operation: String,
+    pub event: String,
+    // path of the script relative to post scripts folder
+    pub script: String,
+    pub arguments: Vec<String>,
+    pub client: String,
+    pub payload: String,
+    pub attempts: u32,
+    pub error: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -24,11 +102,77 @@ pub struct Client {
 
     pub payments: Vec<Payment>,
 
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adjustments: Vec<Adjustment>,
+
     pub info: Option<String>,
+
+    // removed clients are archived so their payments still count in reports
+    #[serde(
+        default,
+        with = "datetime_serializer::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub archived_at: Option<DateTime<Utc>>,
+
+    // unknown for clients added before it was recorded
+    #[serde(
+        default,
+        with = "datetime_serializer::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub created_at: Option<DateTime<Utc>>,
+
+    #[serde(flatten)]
+    pub profile: Profile,
+
+    // sorted by name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+// contact details and other optional fields of a client that can be edited
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contact: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telegram: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referred_by: Option<String>,
+}
+
+impl Profile {
+    // text of every field for searching
+    pub fn fields(&self) -> [&Option<String>; 5] {
+        [
+            &self.contact,
+            &self.telegram,
+            &self.email,
+            &self.notes,
+            &self.referred_by,
+        ]
+    }
 }
 
 impl Client {
-    fn new(name: &str, days: u32, seller: &str, money: u32, info: &str) -> Client {
+    fn new(
+        name: &str,
+        days: u32,
+        seller: &str,
+        money: u32,
+        plan: Option<&str>,
+        info: &str,
+    ) -> Client {
         let now_date = Utc::now();
         let expire_time = now_date + Duration::days(days.into());
 
@@ -39,16 +183,290 @@ impl Client {
                 seller: seller.to_string(),
                 money,
                 date: now_date,
+                days: Some(days),
+                plan: plan.map(str::to_string),
             }],
+            adjustments: Vec::new(),
             info: Some(info.to_string()),
+            archived_at: None,
+            created_at: Some(now_date),
+            profile: Profile::default(),
+            tags: Vec::new(),
+        }
+    }
+}
+
+pub struct PaymentRecord {
+    pub client_name: String,
+    pub payment: Payment,
+}
+
+#[derive(Default)]
+pub struct PaymentFilter {
+    pub client_name: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl PaymentFilter {
+    fn matches(&self, client_name: &str, payment: &Payment) -> bool {
+        self.client_name
+            .as_ref()
+            .is_none_or(|name| name == client_name)
+            && self.since.is_none_or(|since| payment.date >= since)
+            && self.until.is_none_or(|until| payment.date < until)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl Period {
+    // same format works for both chrono and sqlite strftime
+    fn format(&self) -> &'static str {
+        match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Week => "%Y-W%W",
+            Period::Month => "%Y-%m",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum RevenueGroup {
+    Seller,
+    Plan,
+}
+
+impl RevenueGroup {
+    // payments without plan are grouped under "-"
+    fn key(&self, payment: &Payment) -> String {
+        match self {
+            RevenueGroup::Seller => payment.seller.clone(),
+            RevenueGroup::Plan => payment.plan.clone().unwrap_or("-".to_string()),
+        }
+    }
+}
+
+pub struct RevenueRow {
+    pub period: String,
+    pub group: String,
+    pub total: u32,
+    pub count: u32,
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum ClientSort {
+    Name,
+    #[default]
+    Expire,
+    LastPayment,
+    TotalPaid,
+}
+
+#[derive(Default)]
+pub struct ClientQuery {
+    pub archived: bool,
+    pub expired: bool,
+    pub expiring_within: Option<u32>,
+    pub seller: Option<String>,
+    pub info_contains: Option<String>,
+    pub name_glob: Option<String>,
+    // case insensitive text in name, info or profile
+    pub search: Option<String>,
+    pub tag: Option<String>,
+    pub sort: ClientSort,
+    pub limit: Option<usize>,
+}
+
+impl ClientQuery {
+    fn matches(&self, client: &Client, now_date: DateTime<Utc>) -> bool {
+        let is_expired = client.expire_time < now_date;
+        let expire_matches = match (self.expired, self.expiring_within) {
+            (false, None) => true,
+            (expired, expiring_within) => {
+                (expired && is_expired)
+                    || expiring_within.is_some_and(|days| {
+                        !is_expired && client.expire_time < now_date + Duration::days(days.into())
+                    })
+            }
+        };
+
+        expire_matches
+            && self
+                .seller
+                .as_ref()
+                .is_none_or(|seller| &client.payments.last().unwrap().seller == seller)
+            && self.info_contains.as_ref().is_none_or(|text| {
+                client
+                    .info
+                    .as_ref()
+                    .is_some_and(|info| info.contains(text.as_str()))
+            })
+            && self
+                .name_glob
+                .as_ref()
+                .is_none_or(|pattern| glob_match(pattern, &client.name))
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| client.tags.contains(tag))
+            // sqlite lower() only folds ascii letters
+            && self.search.as_ref().is_none_or(|text| {
+                let text = text.to_ascii_lowercase();
+                [&Some(client.name.clone()), &client.info]
+                    .into_iter()
+                    .chain(client.profile.fields())
+                    .flatten()
+                    .any(|field| field.to_ascii_lowercase().contains(&text))
+            })
+    }
+
+    fn sort(&self, clients: &mut [Client]) {
+        // stable sorts, so clients with equal keys stay sorted by name
+        clients.sort_by(|a, b| a.name.cmp(&b.name));
+        match self.sort {
+            ClientSort::Name => {}
+            ClientSort::Expire => clients.sort_by_key(|client| Reverse(client.expire_time)),
+            ClientSort::LastPayment => {
+                clients.sort_by_key(|client| Reverse(client.payments.last().unwrap().date))
+            }
+            ClientSort::TotalPaid => clients.sort_by_key(|client| {
+                Reverse(client.payments.iter().map(|p| p.money).sum::<u32>())
+            }),
         }
     }
 }
 
+enum GlobToken {
+    Any,
+    One,
+    Char(char),
+    // `[...]` as ranges, `[^...]` is negated
+    Class(bool, Vec<(char, char)>),
+}
+
+// None when a class isn't closed, sqlite GLOB matches nothing with such a pattern
+fn parse_glob(pattern: &str) -> Option<Vec<GlobToken>> {
+    let mut chars = pattern.chars().peekable();
+    let mut tokens = Vec::new();
+    while let Some(ch) = chars.next() {
+        tokens.push(match ch {
+            '*' => GlobToken::Any,
+            '?' => GlobToken::One,
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                // `]` right after the opening is a member, not the end
+                if let Some(bracket) = chars.next_if_eq(&']') {
+                    ranges.push((bracket, bracket));
+                }
+                loop {
+                    match chars.next()? {
+                        ']' => break,
+                        start if chars.peek() == Some(&'-') => {
+                            chars.next();
+                            match chars.next()? {
+                                // `-` before the closing is a member
+                                ']' => {
+                                    ranges.extend([(start, start), ('-', '-')]);
+                                    break;
+                                }
+                                end => ranges.push((start, end)),
+                            }
+                        }
+                        member => ranges.push((member, member)),
+                    }
+                }
+                GlobToken::Class(negated, ranges)
+            }
+            _ => GlobToken::Char(ch),
+        });
+    }
+    Some(tokens)
+}
+
+// same syntax as sqlite GLOB, '*' matches anything, '?' one character and '[...]' one of a class
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let Some(pattern) = parse_glob(pattern) else {
+        return false;
+    };
+    let text: Vec<char> = text.chars().collect();
+    let matches_one = |token: &GlobToken, ch: char| match token {
+        GlobToken::Any => false,
+        GlobToken::One => true,
+        GlobToken::Char(expected) => *expected == ch,
+        GlobToken::Class(negated, ranges) => {
+            ranges
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&ch))
+                != *negated
+        }
+    };
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && matches_one(&pattern[p], text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && matches!(pattern[p], GlobToken::Any) {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..]
+        .iter()
+        .all(|token| matches!(token, GlobToken::Any))
+}
+
 pub enum Target {
     All,
     MatchInfo(String),
     OnePerson(String),
+    Names(Vec<String>),
+    NameGlob(String),
+    // seller of the last payment
+    Seller(String),
+    ExpiringWithin(u32),
+    Tag(String),
+    InfoContains(String),
+}
+
+impl Target {
+    // archived clients are never targeted
+    pub fn matches(&self, client: &Client) -> bool {
+        client.archived_at.is_none()
+            && match self {
+                Target::All => true,
+                Target::MatchInfo(info) => client.info.as_ref() == Some(info),
+                Target::OnePerson(name) => &client.name == name,
+                Target::Names(names) => names.contains(&client.name),
+                Target::NameGlob(pattern) => glob_match(pattern, &client.name),
+                Target::Seller(seller) => &client.payments.last().unwrap().seller == seller,
+                Target::ExpiringWithin(days) => {
+                    let now_date = Utc::now();
+                    client.expire_time >= now_date
+                        && client.expire_time < now_date + Duration::days((*days).into())
+                }
+                Target::Tag(tag) => client.tags.contains(tag),
+                Target::InfoContains(text) => client
+                    .info
+                    .as_ref()
+                    .is_some_and(|info| info.contains(text.as_str())),
+            }
+    }
 }
 
 pub trait Database {
@@ -58,6 +476,7 @@ pub trait Database {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
         info: &str,
     ) -> Result<(), String>;
 
@@ -67,6 +486,7 @@ pub trait Database {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
     ) -> Result<(), String>;
 
     fn edit_client(
@@ -75,14 +495,140 @@ pub trait Database {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
         info: &str,
     ) -> Result<(), String>;
 
-    fn renew_all_clients(&mut self, days: u32) -> Result<(), String>;
+    // expired clients are skipped
+    // days are given for free, so each client gets an adjustment with the reason
+    fn renew_all_clients(&mut self, days: u32, target: Target, reason: &str) -> Result<(), String>;
+    // moves expire time of targeted clients, expired ones too, and records it as an adjustment
+    fn shift_expire_times(&mut self, days: i64, target: Target, reason: &str)
+        -> Result<(), String>;
+    // archives the client, payments are kept until it's purged
     fn remove_client(&mut self, name: &str) -> Result<(), String>;
+    fn restore_client(&mut self, name: &str) -> Result<(), String>;
+
+    // deletes clients archived before the given date with their payments, returns their names
+    fn purge_clients(&mut self, archived_before: DateTime<Utc>) -> Result<Vec<String>, String>;
+
     fn list_clients(&self) -> Result<Vec<Client>, String>;
+    fn list_archived_clients(&self) -> Result<Vec<Client>, String>;
+
+    // backends that can filter natively should override this
+    fn query_clients(&self, query: &ClientQuery) -> Result<Vec<Client>, String> {
+        let now_date = Utc::now();
+        let clients = if query.archived {
+            self.list_archived_clients()?
+        } else {
+            self.list_clients()?
+        };
+        let mut clients: Vec<Client> = clients
+            .into_iter()
+            .filter(|client| query.matches(client, now_date))
+            .collect();
+
+        query.sort(&mut clients);
+        if let Some(limit) = query.limit {
+            clients.truncate(limit);
+        }
+        Ok(clients)
+    }
+
+    // writes client with its payments as they are, replacing the client with the same name
+    fn import_client(&mut self, client: &Client) -> Result<(), String>;
+
     fn rename_client(&mut self, old_name: &str, new_name: &str) -> Result<(), String>;
     fn set_client_info(&mut self, target: Target, info: &str) -> Result<(), String>;
+    fn set_client_profile(&mut self, name: &str, profile: &Profile) -> Result<(), String>;
+
+    // tags that client already has or doesn't have are ignored
+    fn add_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String>;
+    fn remove_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String>;
+    // every tag of active clients with the number of clients that have it
+    fn list_tags(&self) -> Result<Vec<(String, usize)>, String> {
+        let mut tags: BTreeMap<String, usize> = BTreeMap::new();
+        for client in self.list_clients()? {
+            for tag in client.tags {
+                *tags.entry(tag).or_default() += 1;
+            }
+        }
+        Ok(tags.into_iter().collect())
+    }
     fn get_client_info(&self, name: &str) -> Result<String, String>;
+    fn list_payments(&self, filter: &PaymentFilter) -> Result<Vec<PaymentRecord>, String>;
+
+    // backends that can aggregate natively should override this
+    fn revenue(
+        &self,
+        period: Period,
+        group: RevenueGroup,
+        filter: &PaymentFilter,
+    ) -> Result<Vec<RevenueRow>, String> {
+        let mut groups: BTreeMap<(String, String), RevenueRow> = BTreeMap::new();
+        for record in self.list_payments(filter)? {
+            let payment = record.payment;
+            let period = payment.date.format(period.format()).to_string();
+            let key = group.key(&payment);
+            let row = groups
+                .entry((period.clone(), key.clone()))
+                .or_insert(RevenueRow {
+                    period,
+                    group: key,
+                    total: 0,
+                    count: 0,
+                });
+            row.total += payment.money;
+            row.count += 1;
+        }
+
+        Ok(groups.into_values().collect())
+    }
+
+    fn add_seller(&mut self, name: &str) -> Result<(), String>;
+    fn remove_seller(&mut self, name: &str) -> Result<(), String>;
+    fn rename_seller(&mut self, old_name: &str, new_name: &str) -> Result<(), String>;
+    fn list_sellers(&self) -> Result<Vec<String>, String>;
+
+    fn add_plan(&mut self, plan: &Plan) -> Result<(), String>;
+    // payments keep the name of removed plan
+    fn remove_plan(&mut self, name: &str) -> Result<(), String>;
+    fn list_plans(&self) -> Result<Vec<Plan>, String>;
+
+    // `id` of the hook is ignored and a new one is assigned
+    fn add_pending_hook(&mut self, hook: &PendingHook) -> Result<(), String>;
+    fn update_pending_hook(&mut self, hook: &PendingHook) -> Result<(), String>;
+    fn remove_pending_hook(&mut self, id: u64) -> Result<(), String>;
+    fn list_pending_hooks(&self) -> Result<Vec<PendingHook>, String>;
+
+    fn add_notification(&mut self, notification: &Notification) -> Result<(), String>;
+    fn is_notified(
+        &self,
+        client_name: &str,
+        threshold: u32,
+        expire_time: &DateTime<Utc>,
+    ) -> Result<bool, String>;
+
+    // records changes made from now until commit as one audit entry
+    fn begin_operation(&mut self, operation: &str, arguments: &[String]);
+    fn list_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String>;
+
+    // clients changed since the operation began, before the changes are committed
+    fn pending_changes(&self) -> Result<Vec<ClientChange>, String>;
+
+    // puts every client changed by the operation back to its snapshot from before the operation
+    fn revert_changes(&mut self, entry: &AuditEntry) -> Result<(), String>;
+
+    // undoes operation with given id or the most recent one, returns the undone operation
+    fn undo(&mut self, id: Option<u64>) -> Result<AuditEntry, String> {
+        let entries = self.list_audit_entries(&AuditFilter::default())?;
+        let entry = audit::find_undo_target(&entries, id)?;
+        self.revert_changes(&entry)?;
+        Ok(entry)
+    }
+
     fn commit(self) -> Result<(), String>;
+
+    // drops every change made since database is opened
+    fn rollback(self) -> Result<(), String>;
 }
@@ -0,0 +1,225 @@
+use crate::db::{datetime_serializer, Client};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{env, fs};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClientChange {
+    pub before: Option<Client>,
+    pub after: Option<Client>,
+}
+
+impl ClientChange {
+    pub fn touches(&self, name: &str) -> bool {
+        [&self.before, &self.after]
+            .iter()
+            .any(|client| client.as_ref().is_some_and(|client| client.name == name))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub id: u64,
+
+    #[serde(with = "datetime_serializer")]
+    pub date: DateTime<Utc>,
+
+    // only advisory, see `current_user`
+    pub user: String,
+    pub operation: String,
+    pub arguments: Vec<String>,
+    pub changes: Vec<ClientChange>,
+
+    // id of the operation this one undid
+    #[serde(default)]
+    pub reverts: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct AuditFilter {
+    pub client_name: Option<String>,
+    pub operation: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.operation
+            .as_ref()
+            .is_none_or(|operation| &entry.operation == operation)
+            && self
+                .client_name
+                .as_ref()
+                .is_none_or(|name| entry.changes.iter().any(|change| change.touches(name)))
+    }
+}
+
+// keeps snapshot of every client before the first time it's touched by current operation,
+// so the audit entry can be written with before and after images on commit
+#[derive(Default)]
+pub struct ChangeTracker {
+    operation: Option<(String, Vec<String>)>,
+    reverts: Option<u64>,
+    // snapshot before the operation and the name client has now
+    changes: Vec<(Option<Client>, String)>,
+}
+
+impl ChangeTracker {
+    pub fn begin(&mut self, operation: &str, arguments: &[String]) {
+        self.operation = Some((operation.to_string(), arguments.to_vec()));
+    }
+
+    pub fn is_tracked(&self, name: &str) -> bool {
+        self.changes
+            .iter()
+            .any(|(_, current_name)| current_name == name)
+    }
+
+    pub fn track(&mut self, name: &str, before: Option<Client>) {
+        if !self.is_tracked(name) {
+            self.changes.push((before, name.to_string()));
+        }
+    }
+
+    pub fn reverts(&mut self, id: u64) {
+        self.reverts = Some(id);
+    }
+
+    pub fn rename(&mut self, old_name: &str, new_name: &str) {
+        for (_, current_name) in self.changes.iter_mut() {
+            if current_name == old_name {
+                *current_name = new_name.to_string();
+            }
+        }
+    }
+
+    // `after` must return snapshot of clients by their current name
+    pub fn changes<F>(&self, mut after: F) -> Result<Vec<ClientChange>, String>
+    where
+        F: FnMut(&str) -> Result<Option<Client>, String>,
+    {
+        let mut changes = Vec::new();
+        for (before, current_name) in &self.changes {
+            changes.push(ClientChange {
+                before: before.clone(),
+                after: after(current_name)?,
+            });
+        }
+        Ok(changes)
+    }
+
+    // returns None when there is no operation to record
+    pub fn into_entry<F>(self, after: F) -> Result<Option<AuditEntry>, String>
+    where
+        F: FnMut(&str) -> Result<Option<Client>, String>,
+    {
+        let changes = self.changes(after)?;
+        let (operation, arguments) = match self.operation {
+            Some(operation) => operation,
+            None => return Ok(None),
+        };
+
+        Ok(Some(AuditEntry {
+            id: 0,
+            date: Utc::now(),
+            user: current_user(),
+            operation,
+            arguments,
+            changes,
+            reverts: self.reverts,
+        }))
+    }
+}
+
+// `entries` must be every entry of audit log in order, picks the most recent operation that can
+// be undone when `id` is not given
+pub fn find_undo_target(entries: &[AuditEntry], id: Option<u64>) -> Result<AuditEntry, String> {
+    let reverted: Vec<u64> = entries.iter().filter_map(|entry| entry.reverts).collect();
+    let is_active = |entry: &AuditEntry| entry.reverts.is_none() && !reverted.contains(&entry.id);
+
+    let target = match id {
+        Some(id) => entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .ok_or(format!("operation {id} doesn't exists!"))?,
+        None => entries
+            .iter()
+            .rev()
+            .find(|entry| is_active(entry) && !entry.changes.is_empty())
+            .ok_or("there is no operation to undo".to_string())?,
+    };
+
+    if target.reverts.is_some() {
+        return Err(format!("operation {} is an undo itself", target.id));
+    }
+    if reverted.contains(&target.id) {
+        return Err(format!("operation {} is already undone", target.id));
+    }
+    if target.changes.is_empty() {
+        return Err(format!(
+            "operation {} didn't change any client, nothing to undo",
+            target.id
+        ));
+    }
+    // only clients are kept in audit log, reverting them alone leaves payments out of sync with sellers
+    if ["seller-", "plan-"]
+        .iter()
+        .any(|prefix| target.operation.starts_with(prefix))
+    {
+        return Err(format!(
+            "operation {} ({}) changed sellers or plans, it cannot be undone",
+            target.id, target.operation
+        ));
+    }
+
+    let names: Vec<&str> = target
+        .changes
+        .iter()
+        .flat_map(|change| [&change.before, &change.after])
+        .flatten()
+        .map(|client| client.name.as_str())
+        .collect();
+    for later_entry in entries
+        .iter()
+        .filter(|entry| entry.id > target.id && is_active(entry))
+    {
+        if let Some(name) = names.iter().find(|name| {
+            later_entry
+                .changes
+                .iter()
+                .any(|change| change.touches(name))
+        }) {
+            return Err(format!(
+                "cannot undo operation {}: client '{name}' is changed later by operation {} ({})",
+                target.id, later_entry.id, later_entry.operation
+            ));
+        }
+    }
+
+    Ok(target.clone())
+}
+
+// it's advisory, USER and LOGNAME are whatever the caller sets them to,
+// when both are missing the owner of process is recorded and "unknown" if even that can't be found
+pub fn current_user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .ok()
+        .filter(|user| !user.is_empty())
+        .or_else(process_owner)
+        .unwrap_or("unknown".to_string())
+}
+
+#[cfg(unix)]
+fn process_owner() -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let uid = fs::metadata("/proc/self").ok()?.uid();
+    let hostname = fs::read_to_string("/proc/sys/kernel/hostname").unwrap_or_default();
+    Some(format!("uid {uid}@{}", hostname.trim()))
+}
+
+#[cfg(not(unix))]
+fn process_owner() -> Option<String> {
+    None
+}
@@ -0,0 +1,111 @@
+use crate::db::migrations;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// ids sort the same as the time they're taken at
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+// a copy of sqlite database, stored as `<id>_<label>.db` in backups folder
+pub struct Backup {
+    pub id: String,
+    // operation it's taken before, or why it's taken
+    pub label: String,
+    pub date: DateTime<Utc>,
+    pub size: u64,
+    pub path: PathBuf,
+}
+
+pub fn list(backups_path: &Path) -> Result<Vec<Backup>, String> {
+    if !backups_path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(backups_path).map_err(|e| format!("cannot list backups: {e}"))?;
+    let mut backups = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("cannot list backups: {e}"))?
+            .path();
+        // files that aren't made by us are left alone
+        let Some((id, label)) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".db"))
+            .and_then(|stem| stem.split_once('_'))
+        else {
+            continue;
+        };
+        let Ok(date) = NaiveDateTime::parse_from_str(id, ID_FORMAT) else {
+            continue;
+        };
+        backups.push(Backup {
+            id: id.to_string(),
+            label: label.to_string(),
+            date: DateTime::from_utc(date, Utc),
+            size: fs::metadata(&path).map_or(0, |metadata| metadata.len()),
+            path,
+        });
+    }
+    backups.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(backups)
+}
+
+pub fn find(backups_path: &Path, id: &str) -> Result<Backup, String> {
+    list(backups_path)?
+        .into_iter()
+        .find(|backup| backup.id == id)
+        .ok_or(format!("backup '{id}' doesn't exist"))
+}
+
+// uses online backup api, so it's consistent even while another process writes
+pub fn create(conn: &Connection, backups_path: &Path, label: &str) -> Result<Backup, String> {
+    fs::create_dir_all(backups_path).map_err(|e| format!("cannot create backups folder: {e}"))?;
+    let date = Utc::now();
+    let id = date.format(ID_FORMAT).to_string();
+    let path = backups_path.join(format!("{id}_{label}.db"));
+    conn.backup(DatabaseName::Main, &path, None)
+        .map_err(|e| format!("cannot back up database: {e}"))?;
+
+    Ok(Backup {
+        id,
+        label: label.to_string(),
+        date,
+        size: fs::metadata(&path).map_or(0, |metadata| metadata.len()),
+        path,
+    })
+}
+
+// removes all but the newest `keep` backups
+pub fn rotate(backups_path: &Path, keep: usize) -> Result<(), String> {
+    let backups = list(backups_path)?;
+    let old_count = backups.len().saturating_sub(keep);
+    for backup in &backups[..old_count] {
+        fs::remove_file(&backup.path)
+            .map_err(|e| format!("cannot remove backup '{}': {e}", backup.id))?;
+    }
+    Ok(())
+}
+
+pub fn verify(backup: &Backup) -> Result<(), String> {
+    let damaged = |e: String| format!("backup '{}' is damaged: {e}", backup.id);
+    let conn = Connection::open_with_flags(&backup.path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| damaged(e.to_string()))?;
+    let result: String = conn
+        .query_row("PRAGMA integrity_check", (), |row| row.get(0))
+        .map_err(|e| damaged(e.to_string()))?;
+    if result != "ok" {
+        return Err(damaged(result));
+    }
+    migrations::pending(&conn)?;
+    Ok(())
+}
+
+// replaces content of database in place, so other connections never see a half copied file
+pub fn restore(conn: &mut Connection, backup: &Backup) -> Result<(), String> {
+    conn.restore(DatabaseName::Main, &backup.path, None::<fn(_)>)
+        .map_err(|e| format!("cannot restore backup '{}': {e}", backup.id))
+}
@@ -1,7 +1,7 @@
 use chrono::{DateTime, TimeZone, Utc};
 use serde::{self, Deserialize, Deserializer, Serializer};
 
-const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
 pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -16,8 +16,11 @@ where
     D: Deserializer<'a>,
 {
     let date_string = String::deserialize(deserializer)?;
-    Utc.datetime_from_str(&date_string, FORMAT)
-        .map_err(serde::de::Error::custom)
+    parse(&date_string).map_err(serde::de::Error::custom)
+}
+
+pub fn parse(date_str: &str) -> chrono::ParseResult<DateTime<Utc>> {
+    Utc.datetime_from_str(date_str, FORMAT)
 }
 
 pub fn datetime_to_str(date: &DateTime<Utc>) -> String {
@@ -27,3 +30,29 @@ pub fn datetime_to_str(date: &DateTime<Utc>) -> String {
 pub fn datetime_from_str(date_str: &str) -> DateTime<Utc> {
     Utc.datetime_from_str(date_str, FORMAT).unwrap()
 }
+
+// same format for optional dates, use with `#[serde(default)]`
+pub mod option {
+    use chrono::{DateTime, Utc};
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => super::serialize(date, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'a, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'a>,
+    {
+        let date_string: Option<String> = Option::deserialize(deserializer)?;
+        date_string
+            .map(|date_string| super::parse(&date_string).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
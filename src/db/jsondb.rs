@@ -1,30 +1,235 @@
-use crate::db::{Client, Database, Payment, Target};
-use chrono::{Duration, Utc};
-use std::{fs, path::PathBuf};
+use crate::db::{
+    audit::{AuditEntry, AuditFilter, ChangeTracker, ClientChange},
+    Adjustment, Client, Database, Notification, Payment, PaymentFilter, PaymentRecord, PendingHook,
+    Plan, Profile, Target, INITIAL_SELLERS,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct JsonData {
+    clients: Vec<Client>,
+    sellers: Vec<String>,
+
+    #[serde(default)]
+    plans: Vec<Plan>,
+
+    #[serde(default)]
+    audit_log: Vec<AuditEntry>,
+
+    #[serde(default)]
+    pending_hooks: Vec<PendingHook>,
+
+    // only goes up, so ids of retried hooks aren't given again like sqlite's autoincrement
+    #[serde(default)]
+    next_hook_id: u64,
+
+    #[serde(default)]
+    notifications: Vec<Notification>,
+}
+
+impl Default for JsonData {
+    fn default() -> Self {
+        JsonData {
+            clients: Vec::new(),
+            sellers: INITIAL_SELLERS.iter().map(|s| s.to_string()).collect(),
+            plans: Vec::new(),
+            audit_log: Vec::new(),
+            pending_hooks: Vec::new(),
+            next_hook_id: 1,
+            notifications: Vec::new(),
+        }
+    }
+}
+
+// files written before sellers were stored in database only contain an array of clients
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonFile {
+    Legacy(Vec<Client>),
+    Current(JsonData),
+}
 
 pub struct JsonDb {
     file_path: PathBuf,
-    clients: Option<Vec<Client>>,
+    data: Option<JsonData>,
+    tracker: ChangeTracker,
+    // held until the db is dropped, so a second process can't interleave its changes,
+    // None when it's opened read only
+    lock_path: Option<PathBuf>,
+    // content of the file when it was opened, commit refuses if someone changed it meanwhile
+    opened_hash: u64,
 }
 
 impl JsonDb {
-    #[allow(dead_code)]
     pub fn new(file_path: PathBuf) -> Result<JsonDb, String> {
-        if !file_path.is_file() {
-            fs::write(&file_path, "[]").map_err(|e| {
+        // dropping the db on any error below releases the lock again
+        let mut db = JsonDb {
+            lock_path: Some(Self::lock(&file_path)?),
+            file_path,
+            data: None,
+            tracker: ChangeTracker::default(),
+            opened_hash: 0,
+        };
+        if !db.file_path.is_file() {
+            let json_string = serde_json::to_string_pretty(&JsonData::default()).unwrap();
+            db.write_atomically(json_string.as_bytes()).map_err(|e| {
                 format!(
-                    "cannot create database file at '{}': {}",
-                    file_path.to_str().unwrap(),
-                    e.to_string()
+                    "cannot create database file at '{}': {e}",
+                    db.file_path.to_str().unwrap(),
                 )
             })?;
         }
+        db.opened_hash = Self::hash_file(&db.file_path)?;
+
+        Ok(db)
+    }
 
-        Ok(JsonDb {
+    // reads a file without locking it, e.g. to import from, it can't be committed
+    pub fn open_read_only(file_path: PathBuf) -> Result<JsonDb, String> {
+        let mut db = JsonDb {
+            lock_path: None,
             file_path,
-            clients: None,
+            data: None,
+            tracker: ChangeTracker::default(),
+            opened_hash: 0,
+        };
+        db.data = Some(db.load()?);
+        Ok(db)
+    }
+
+    fn lock(file_path: &Path) -> Result<PathBuf, String> {
+        let mut lock_path = file_path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock_path = PathBuf::from(lock_path);
+
+        let mut lock_file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let owner = fs::read_to_string(&lock_path).unwrap_or_default();
+                return Err(format!(
+                    "database is locked by another process (pid {}), remove '{}' if it isn't running",
+                    owner.trim(),
+                    lock_path.to_str().unwrap()
+                ));
+            }
+            Err(e) => {
+                return Err(format!(
+                    "cannot create lock file '{}': {e}",
+                    lock_path.to_str().unwrap()
+                ))
+            }
+        };
+        // the pid is only there to help whoever finds a stale lock
+        let _ = write!(lock_file, "{}", process::id());
+        Ok(lock_path)
+    }
+
+    fn hash_file(file_path: &Path) -> Result<u64, String> {
+        let content = fs::read(file_path).map_err(|error| {
+            format!(
+                "cannot open file '{}': {error}",
+                file_path.to_str().unwrap(),
+            )
+        })?;
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    // the new content is fully on disk before it replaces the old file, so a crash leaves one of them
+    fn write_atomically(&self, content: &[u8]) -> Result<(), String> {
+        let file_name = self.file_path.to_str().unwrap();
+        let mut temp_path = self.file_path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        let write_temp = || -> std::io::Result<()> {
+            let mut temp_file = fs::File::create(&temp_path)?;
+            temp_file.write_all(content)?;
+            temp_file.sync_all()
+        };
+        if let Err(error) = write_temp() {
+            let _ = fs::remove_file(&temp_path);
+            return Err(format!("cannot write to file '{file_name}': {error}"));
+        }
+        fs::rename(&temp_path, &self.file_path)
+            .map_err(|error| format!("cannot replace file '{file_name}': {error}"))?;
+
+        // the rename itself is only durable after the directory is synced
+        if let Some(directory) = self
+            .file_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+        {
+            fs::File::open(directory)
+                .and_then(|directory| directory.sync_all())
+                .map_err(|error| format!("cannot sync directory of '{file_name}': {error}"))?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Result<JsonData, String> {
+        if let Some(data) = &self.data {
+            return Ok(data.clone());
+        }
+
+        let file = fs::File::open(&self.file_path).map_err(|error| {
+            format!(
+                "cannot open file '{}': {error}",
+                self.file_path.to_str().unwrap(),
+            )
+        })?;
+        let json_file: JsonFile =
+            serde_json::from_reader(file).map_err(|error| format!("cannot parse json: {error}"))?;
+
+        Ok(match json_file {
+            JsonFile::Legacy(clients) => JsonData {
+                clients,
+                ..Default::default()
+            },
+            JsonFile::Current(data) => data,
         })
     }
+
+    // archived clients included
+    fn all_clients(&self) -> Result<Vec<Client>, String> {
+        Ok(self.load()?.clients)
+    }
+
+    fn get_client(&self, name: &str) -> Result<Option<Client>, String> {
+        Ok(self
+            .all_clients()?
+            .into_iter()
+            .find(|client| client.name == name))
+    }
+
+    fn track(&mut self, name: &str) -> Result<(), String> {
+        if !self.tracker.is_tracked(name) {
+            let before = self.get_client(name)?;
+            self.tracker.track(name, before);
+        }
+        Ok(())
+    }
+
+    fn save_clients(&mut self, clients: Vec<Client>) -> Result<(), String> {
+        let mut data = self.load()?;
+        data.clients = clients;
+        self.data = Some(data);
+        Ok(())
+    }
 }
 
 impl Database for JsonDb {
@@ -34,17 +239,23 @@ impl Database for JsonDb {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
         info: &str,
     ) -> Result<(), String> {
-        let mut clients: Vec<Client> = self.list_clients()?;
-        if clients.iter().any(|exist_client| exist_client.name == name) {
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        if let Some(exist_client) = clients.iter().find(|client| client.name == name) {
+            if exist_client.archived_at.is_some() {
+                return Err(format!(
+                    "client '{name}' is archived, restore or purge it first"
+                ));
+            }
             return Err(format!("client '{}' already exists!", name));
         }
 
-        let client = Client::new(name, days, &seller, money, info);
+        let client = Client::new(name, days, seller, money, plan, info);
         clients.push(client);
-        self.clients = Some(clients);
-        Ok(())
+        self.save_clients(clients)
     }
 
     fn renew_client(
@@ -53,14 +264,19 @@ impl Database for JsonDb {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
     ) -> Result<(), String> {
-        let mut clients: Vec<Client> = self.list_clients()?;
-        let index = match clients.iter().position(|client| client.name == name) {
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let index = match clients
+            .iter()
+            .position(|client| client.name == name && client.archived_at.is_none())
+        {
             Some(index) => index,
             None => return Err(format!("client with name '{}' doesn't exists!", name)),
         };
 
-        let mut client = &mut clients[index];
+        let client = &mut clients[index];
         let now_date = Utc::now();
 
         if now_date > client.expire_time {
@@ -72,94 +288,231 @@ impl Database for JsonDb {
             seller: seller.to_string(),
             money,
             date: now_date,
+            days: Some(days),
+            plan: plan.map(str::to_string),
         });
-        self.clients = Some(clients);
-        Ok(())
+        self.save_clients(clients)
     }
 
-    fn renew_all_clients(&mut self, days: u32) -> Result<(), String> {
-        let mut clients = self.list_clients()?;
+    fn renew_all_clients(&mut self, days: u32, target: Target, reason: &str) -> Result<(), String> {
+        let mut clients = self.all_clients()?;
         let now_date = Utc::now();
 
         for client in clients.iter_mut() {
             let is_expired = client.expire_time < now_date;
-            if is_expired {
+            if is_expired || !target.matches(client) {
                 continue;
             }
 
+            self.tracker.track(&client.name, Some(client.clone()));
             client.expire_time += Duration::days(days.into());
+            client
+                .adjustments
+                .push(Adjustment::new(days.into(), reason));
         }
 
-        self.clients = Some(clients);
-        Ok(())
+        self.save_clients(clients)
+    }
+
+    fn shift_expire_times(
+        &mut self,
+        days: i64,
+        target: Target,
+        reason: &str,
+    ) -> Result<(), String> {
+        let mut clients = self.all_clients()?;
+
+        for client in clients.iter_mut() {
+            if !target.matches(client) {
+                continue;
+            }
+
+            self.tracker.track(&client.name, Some(client.clone()));
+            client.expire_time += Duration::days(days);
+            client.adjustments.push(Adjustment::new(days, reason));
+        }
+
+        self.save_clients(clients)
     }
 
     fn edit_client(
         &mut self,
-        _name: &str,
-        _days: u32,
-        _seller: &str,
-        _money: u32,
-        _info: &str,
+        name: &str,
+        days: u32,
+        seller: &str,
+        money: u32,
+        plan: Option<&str>,
+        info: &str,
     ) -> Result<(), String> {
-        unimplemented!();
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let client = clients
+            .iter_mut()
+            .find(|client| client.name == name && client.archived_at.is_none())
+            .ok_or(format!("client with name '{}' doesn't exists!", name))?;
+
+        let expire_time = Utc::now() + Duration::days(days.into());
+        // part of a day is lost when days left are shown, so it's not an adjustment
+        let adjusted_days = (expire_time - client.expire_time).num_days();
+        if adjusted_days != 0 {
+            client
+                .adjustments
+                .push(Adjustment::new(adjusted_days, "edit"));
+        }
+        client.expire_time = expire_time;
+        client.info = Some(info.to_string());
+
+        let last_payment = client.payments.last_mut().ok_or(format!(
+            "payment with client name '{}' doesn't exists!",
+            name
+        ))?;
+        last_payment.seller = seller.to_string();
+        last_payment.money = money;
+        last_payment.plan = plan.map(str::to_string);
+
+        self.save_clients(clients)
     }
 
     fn remove_client(&mut self, name: &str) -> Result<(), String> {
-        let mut clients: Vec<Client> = self.list_clients()?;
-        let index: usize = match clients.iter().position(|client| client.name == name) {
-            Some(index) => index,
-            None => return Err(format!("client with name '{}' doesn't exists!", name)),
-        };
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let client = clients
+            .iter_mut()
+            .find(|client| client.name == name && client.archived_at.is_none())
+            .ok_or(format!("client with name '{}' doesn't exists!", name))?;
 
-        clients.remove(index);
-        self.clients = Some(clients);
-        Ok(())
+        client.archived_at = Some(Utc::now());
+        self.save_clients(clients)
     }
 
-    fn list_clients(&self) -> Result<Vec<Client>, String> {
-        if let Some(clients) = &self.clients {
-            return Ok(clients.clone());
+    fn restore_client(&mut self, name: &str) -> Result<(), String> {
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let client = clients
+            .iter_mut()
+            .find(|client| client.name == name && client.archived_at.is_some())
+            .ok_or(format!("there is no archived client with name '{}'", name))?;
+
+        client.archived_at = None;
+        self.save_clients(clients)
+    }
+
+    fn purge_clients(&mut self, archived_before: DateTime<Utc>) -> Result<Vec<String>, String> {
+        let (purged, kept): (Vec<Client>, Vec<Client>) =
+            self.all_clients()?.into_iter().partition(|client| {
+                client
+                    .archived_at
+                    .is_some_and(|date| date < archived_before)
+            });
+
+        let mut names: Vec<String> = Vec::new();
+        for client in purged {
+            names.push(client.name.clone());
+            self.tracker.track(&client.name.clone(), Some(client));
         }
+        names.sort();
 
-        let file = fs::File::open(&self.file_path).map_err(|error| {
-            format!(
-                "cannot open file '{}': {}",
-                self.file_path.to_str().unwrap(),
-                error.to_string()
-            )
-        })?;
-        serde_json::from_reader(file)
-            .map_err(|error| format!("cannot parse json: {}", error.to_string()))
+        self.save_clients(kept)?;
+        Ok(names)
+    }
+
+    fn list_clients(&self) -> Result<Vec<Client>, String> {
+        Ok(self
+            .all_clients()?
+            .into_iter()
+            .filter(|client| client.archived_at.is_none())
+            .collect())
+    }
+
+    fn list_archived_clients(&self) -> Result<Vec<Client>, String> {
+        Ok(self
+            .all_clients()?
+            .into_iter()
+            .filter(|client| client.archived_at.is_some())
+            .collect())
+    }
+
+    fn import_client(&mut self, client: &Client) -> Result<(), String> {
+        self.track(&client.name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        clients.retain(|exist_client| exist_client.name != client.name);
+        clients.push(client.clone());
+
+        self.save_clients(clients)
     }
 
     fn rename_client(&mut self, old_name: &str, new_name: &str) -> Result<(), String> {
-        let mut clients: Vec<Client> = self.list_clients()?;
-        let mut client = clients
+        self.track(old_name)?;
+        self.tracker.rename(old_name, new_name);
+        let mut clients: Vec<Client> = self.all_clients()?;
+        if clients.iter().any(|client| client.name == new_name) {
+            return Err(format!("client '{}' already exists!", new_name));
+        }
+        let client = clients
             .iter_mut()
-            .find(|client| client.name == old_name)
+            .find(|client| client.name == old_name && client.archived_at.is_none())
             .ok_or(format!("client with name '{}' doesn't exists!", old_name))?;
         client.name = new_name.to_string();
 
-        self.clients = Some(clients);
-        Ok(())
+        self.save_clients(clients)
+    }
+
+    fn set_client_profile(&mut self, name: &str, profile: &Profile) -> Result<(), String> {
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let client = clients
+            .iter_mut()
+            .find(|client| client.name == name && client.archived_at.is_none())
+            .ok_or(format!("client with name '{}' doesn't exists!", name))?;
+        client.profile = profile.clone();
+
+        self.save_clients(clients)
     }
 
     fn set_client_info(&mut self, target: Target, info: &str) -> Result<(), String> {
-        let mut clients: Vec<Client> = self.list_clients()?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        if let Target::OnePerson(name) = &target {
+            if !clients.iter().any(|client| target.matches(client)) {
+                return Err(format!("client with name '{}' doesn't exists!", name));
+            }
+        }
 
         for client in clients.iter_mut() {
-            if let Target::OnePerson(name) = &target {
-                if name != &client.name {
-                    continue;
-                }
+            if !target.matches(client) {
+                continue;
             }
 
+            self.tracker.track(&client.name, Some(client.clone()));
             client.info = Some(info.to_string());
         }
 
-        self.clients = Some(clients);
-        Ok(())
+        self.save_clients(clients)
+    }
+
+    fn add_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String> {
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let client = clients
+            .iter_mut()
+            .find(|client| client.name == name && client.archived_at.is_none())
+            .ok_or(format!("client with name '{}' doesn't exists!", name))?;
+        client.tags.extend(tags.iter().cloned());
+        client.tags.sort();
+        client.tags.dedup();
+
+        self.save_clients(clients)
+    }
+
+    fn remove_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String> {
+        self.track(name)?;
+        let mut clients: Vec<Client> = self.all_clients()?;
+        let client = clients
+            .iter_mut()
+            .find(|client| client.name == name && client.archived_at.is_none())
+            .ok_or(format!("client with name '{}' doesn't exists!", name))?;
+        client.tags.retain(|tag| !tags.contains(tag));
+
+        self.save_clients(clients)
     }
 
     fn get_client_info(&self, name: &str) -> Result<String, String> {
@@ -169,21 +522,270 @@ impl Database for JsonDb {
             return Ok(info.to_string());
         }
 
-        Err(format!("cannot find client with name '{}'", name))
+        Err(format!("client with name '{}' doesn't exists!", name))
     }
 
-    fn commit(self) -> Result<(), String> {
-        if let Some(clients) = self.clients {
-            let json_string = serde_json::to_string_pretty(&clients).unwrap();
-            fs::write(&self.file_path, json_string.as_bytes()).map_err(|error| {
-                format!(
-                    "cannot write to file '{}': {}",
+    fn list_payments(&self, filter: &PaymentFilter) -> Result<Vec<PaymentRecord>, String> {
+        let mut records: Vec<PaymentRecord> = Vec::new();
+        for client in self.all_clients()? {
+            for payment in client.payments {
+                if filter.matches(&client.name, &payment) {
+                    records.push(PaymentRecord {
+                        client_name: client.name.clone(),
+                        payment,
+                    });
+                }
+            }
+        }
+
+        records.sort_by_key(|record| record.payment.date);
+        Ok(records)
+    }
+
+    fn add_seller(&mut self, name: &str) -> Result<(), String> {
+        let mut data = self.load()?;
+        if data.sellers.iter().any(|seller| seller == name) {
+            return Err(format!("seller '{}' already exists!", name));
+        }
+
+        data.sellers.push(name.to_string());
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn remove_seller(&mut self, name: &str) -> Result<(), String> {
+        let mut data = self.load()?;
+        let index = data
+            .sellers
+            .iter()
+            .position(|seller| seller == name)
+            .ok_or(format!("seller with name '{}' doesn't exists!", name))?;
+
+        data.sellers.remove(index);
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn rename_seller(&mut self, old_name: &str, new_name: &str) -> Result<(), String> {
+        let mut data = self.load()?;
+        if data.sellers.iter().any(|seller| seller == new_name) {
+            return Err(format!("seller '{}' already exists!", new_name));
+        }
+
+        let seller = data
+            .sellers
+            .iter_mut()
+            .find(|seller| *seller == old_name)
+            .ok_or(format!("seller with name '{}' doesn't exists!", old_name))?;
+        *seller = new_name.to_string();
+
+        for client in data.clients.iter_mut() {
+            if client
+                .payments
+                .iter()
+                .any(|payment| payment.seller == old_name)
+            {
+                self.tracker.track(&client.name, Some(client.clone()));
+            }
+            for payment in client.payments.iter_mut() {
+                if payment.seller == old_name {
+                    payment.seller = new_name.to_string();
+                }
+            }
+        }
+
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn list_sellers(&self) -> Result<Vec<String>, String> {
+        Ok(self.load()?.sellers)
+    }
+
+    fn add_plan(&mut self, plan: &Plan) -> Result<(), String> {
+        let mut data = self.load()?;
+        if data
+            .plans
+            .iter()
+            .any(|exist_plan| exist_plan.name == plan.name)
+        {
+            return Err(format!("plan '{}' already exists!", plan.name));
+        }
+
+        data.plans.push(plan.clone());
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn remove_plan(&mut self, name: &str) -> Result<(), String> {
+        let mut data = self.load()?;
+        let index = data
+            .plans
+            .iter()
+            .position(|plan| plan.name == name)
+            .ok_or(format!("plan with name '{}' doesn't exists!", name))?;
+
+        data.plans.remove(index);
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn list_plans(&self) -> Result<Vec<Plan>, String> {
+        let mut plans = self.load()?.plans;
+        plans.sort_by(|a, b| (a.days, &a.name).cmp(&(b.days, &b.name)));
+        Ok(plans)
+    }
+
+    fn add_pending_hook(&mut self, hook: &PendingHook) -> Result<(), String> {
+        let mut data = self.load()?;
+        // files written before the counter was stored start after their last hook
+        let id = data.next_hook_id.max(
+            data.pending_hooks
+                .last()
+                .map_or(1, |last_hook| last_hook.id + 1),
+        );
+        data.next_hook_id = id + 1;
+        data.pending_hooks.push(PendingHook { id, ..hook.clone() });
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn update_pending_hook(&mut self, hook: &PendingHook) -> Result<(), String> {
+        let mut data = self.load()?;
+        let exist_hook = data
+            .pending_hooks
+            .iter_mut()
+            .find(|exist_hook| exist_hook.id == hook.id)
+            .ok_or(format!("pending hook {} doesn't exists!", hook.id))?;
+        exist_hook.attempts = hook.attempts;
+        exist_hook.error = hook.error.clone();
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn remove_pending_hook(&mut self, id: u64) -> Result<(), String> {
+        let mut data = self.load()?;
+        let index = data
+            .pending_hooks
+            .iter()
+            .position(|hook| hook.id == id)
+            .ok_or(format!("pending hook {id} doesn't exists!"))?;
+        data.pending_hooks.remove(index);
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn list_pending_hooks(&self) -> Result<Vec<PendingHook>, String> {
+        Ok(self.load()?.pending_hooks)
+    }
+
+    fn add_notification(&mut self, notification: &Notification) -> Result<(), String> {
+        let mut data = self.load()?;
+        data.notifications.push(notification.clone());
+        self.data = Some(data);
+        Ok(())
+    }
+
+    fn is_notified(
+        &self,
+        client_name: &str,
+        threshold: u32,
+        expire_time: &DateTime<Utc>,
+    ) -> Result<bool, String> {
+        Ok(self.load()?.notifications.iter().any(|notification| {
+            notification.client_name == client_name
+                && notification.threshold == threshold
+                && &notification.expire_time == expire_time
+        }))
+    }
+
+    fn begin_operation(&mut self, operation: &str, arguments: &[String]) {
+        self.tracker.begin(operation, arguments);
+    }
+
+    fn list_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String> {
+        let mut entries: Vec<AuditEntry> = self
+            .load()?
+            .audit_log
+            .into_iter()
+            .rev()
+            .filter(|entry| filter.matches(entry))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    fn pending_changes(&self) -> Result<Vec<ClientChange>, String> {
+        self.tracker.changes(|name| self.get_client(name))
+    }
+
+    fn revert_changes(&mut self, entry: &AuditEntry) -> Result<(), String> {
+        let mut clients = self.all_clients()?;
+        // removing everything first so swapped names don't collide
+        for after in entry
+            .changes
+            .iter()
+            .filter_map(|change| change.after.as_ref())
+        {
+            self.track(&after.name)?;
+            clients.retain(|client| client.name != after.name);
+        }
+        for before in entry
+            .changes
+            .iter()
+            .filter_map(|change| change.before.as_ref())
+        {
+            self.track(&before.name)?;
+            if clients.iter().any(|client| client.name == before.name) {
+                return Err(format!("client '{}' already exists!", before.name));
+            }
+            clients.push(before.clone());
+        }
+
+        self.tracker.reverts(entry.id);
+        self.save_clients(clients)
+    }
+
+    fn commit(mut self) -> Result<(), String> {
+        if self.lock_path.is_none() {
+            return Err("database is opened read only".to_string());
+        }
+        let tracker = std::mem::take(&mut self.tracker);
+        if let Some(mut entry) = tracker.into_entry(|name| self.get_client(name))? {
+            let mut data = self.load()?;
+            entry.id = data
+                .audit_log
+                .last()
+                .map_or(1, |last_entry| last_entry.id + 1);
+            data.audit_log.push(entry);
+            self.data = Some(data);
+        }
+
+        if let Some(data) = &self.data {
+            if Self::hash_file(&self.file_path)? != self.opened_hash {
+                return Err(format!(
+                    "'{}' was modified by another program since it was opened, nothing is saved",
                     self.file_path.to_str().unwrap(),
-                    error.to_string()
-                )
-            })?;
+                ));
+            }
+            let json_string = serde_json::to_string_pretty(data).unwrap();
+            self.write_atomically(json_string.as_bytes())?;
         }
 
         Ok(())
     }
+
+    // nothing is written before commit
+    fn rollback(self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Drop for JsonDb {
+    fn drop(&mut self) {
+        if let Some(lock_path) = &self.lock_path {
+            let _ = fs::remove_file(lock_path);
+        }
+    }
 }
@@ -0,0 +1,182 @@
+use rusqlite::Connection;
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    sql: &'static str,
+}
+
+// migrations are applied in order and each one bumps `PRAGMA user_version` to its version,
+// never edit a migration that is released, add a new one instead
+const MIGRATIONS: [Migration; 12] = [
+    Migration {
+        version: 1,
+        description: "create clients and payments tables",
+        // databases created before migrations existed already have these tables
+        sql: "CREATE TABLE IF NOT EXISTS clients (
+                name TEXT PRIMARY KEY,
+                expire_date TEXT NOT NULL,
+                info TEXT
+            );
+            CREATE TABLE IF NOT EXISTS payments (
+                client_name TEXT NOT NULL,
+                seller TEXT NOT NULL,
+                date TEXT NOT NULL,
+                money UNSIGNED INTEGER NOT NULL
+            );",
+    },
+    Migration {
+        version: 2,
+        description: "create sellers table",
+        sql: "CREATE TABLE IF NOT EXISTS sellers (name TEXT PRIMARY KEY);
+            INSERT INTO sellers (name)
+                SELECT name FROM (SELECT 'arian' AS name UNION ALL SELECT 'pouya')
+                WHERE NOT EXISTS (SELECT 1 FROM sellers);",
+    },
+    Migration {
+        version: 3,
+        description: "record days granted by each payment",
+        sql: "ALTER TABLE payments ADD COLUMN days UNSIGNED INTEGER;",
+    },
+    Migration {
+        version: 4,
+        description: "create append-only audit log",
+        sql: "CREATE TABLE audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                date TEXT NOT NULL,
+                user TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                arguments TEXT NOT NULL,
+                changes TEXT NOT NULL
+            );
+            CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+                BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+            CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+                BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;",
+    },
+    Migration {
+        version: 5,
+        description: "record which operation is undone by an audit entry",
+        sql: "ALTER TABLE audit_log ADD COLUMN reverts INTEGER REFERENCES audit_log(id);",
+    },
+    Migration {
+        version: 6,
+        description: "archive removed clients instead of deleting them",
+        sql: "ALTER TABLE clients ADD COLUMN archived_at TEXT;",
+    },
+    Migration {
+        version: 7,
+        description: "create plans table and record plan of payments",
+        sql: "CREATE TABLE plans (
+                name TEXT PRIMARY KEY,
+                days UNSIGNED INTEGER NOT NULL,
+                money UNSIGNED INTEGER NOT NULL
+            );
+            ALTER TABLE payments ADD COLUMN plan TEXT;",
+    },
+    Migration {
+        version: 8,
+        description: "create queue of failed post hooks",
+        sql: "CREATE TABLE pending_hooks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                date TEXT NOT NULL,
+                operation TEXT NOT NULL,
+                event TEXT NOT NULL,
+                script TEXT NOT NULL,
+                arguments TEXT NOT NULL,
+                client TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                attempts UNSIGNED INTEGER NOT NULL,
+                error TEXT NOT NULL
+            );",
+    },
+    Migration {
+        version: 9,
+        description: "record sent expiry notifications",
+        sql: "CREATE TABLE notifications (
+                client_name TEXT NOT NULL,
+                threshold UNSIGNED INTEGER NOT NULL,
+                expire_date TEXT NOT NULL,
+                sent_at TEXT NOT NULL,
+                PRIMARY KEY (client_name, threshold, expire_date)
+            );",
+    },
+    Migration {
+        version: 10,
+        description: "add profile of clients",
+        // first payment is the best guess for when older clients are added
+        sql: "ALTER TABLE clients ADD COLUMN contact TEXT;
+            ALTER TABLE clients ADD COLUMN telegram TEXT;
+            ALTER TABLE clients ADD COLUMN email TEXT;
+            ALTER TABLE clients ADD COLUMN notes TEXT;
+            ALTER TABLE clients ADD COLUMN referred_by TEXT;
+            ALTER TABLE clients ADD COLUMN created_at TEXT;
+            UPDATE clients SET created_at =
+                (SELECT MIN(date) FROM payments WHERE client_name = clients.name);",
+    },
+    Migration {
+        version: 11,
+        description: "create tags of clients",
+        sql: "CREATE TABLE client_tags (
+                client_name TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (client_name, tag)
+            );",
+    },
+    Migration {
+        version: 12,
+        description: "create adjustments of expire time",
+        sql: "CREATE TABLE adjustments (
+                client_name TEXT NOT NULL,
+                date TEXT NOT NULL,
+                days INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                operator TEXT NOT NULL
+            );",
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().unwrap().version
+}
+
+pub fn current_version(conn: &Connection) -> Result<u32, String> {
+    conn.query_row("PRAGMA user_version", (), |row| row.get(0))
+        .map_err(|e| format!("cannot read database schema version: {e}"))
+}
+
+pub fn pending(conn: &Connection) -> Result<Vec<&'static Migration>, String> {
+    let current_version = current_version(conn)?;
+    if current_version > latest_version() {
+        return Err(format!(
+            "database schema version is {current_version} but this build only knows up to {}, please upgrade manjaliof",
+            latest_version()
+        ));
+    }
+
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current_version)
+        .collect())
+}
+
+pub fn migrate(conn: &mut Connection) -> Result<Vec<&'static Migration>, String> {
+    let pending_migrations = pending(conn)?;
+    for migration in &pending_migrations {
+        let error_msg = |e: rusqlite::Error| {
+            format!(
+                "cannot apply migration {} ({}): {e}",
+                migration.version, migration.description
+            )
+        };
+
+        let trans = conn.transaction().map_err(error_msg)?;
+        trans.execute_batch(migration.sql).map_err(error_msg)?;
+        trans
+            .pragma_update(None, "user_version", migration.version)
+            .map_err(error_msg)?;
+        trans.commit().map_err(error_msg)?;
+    }
+
+    Ok(pending_migrations)
+}
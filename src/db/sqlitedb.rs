@@ -1,6 +1,9 @@
 use crate::db::{
+    audit::{AuditEntry, AuditFilter, ChangeTracker, ClientChange},
     datetime_serializer::{datetime_from_str, datetime_to_str},
-    Client, Database, Payment, Target,
+    migrations, Adjustment, Client, ClientQuery, ClientSort, Database, Notification, Payment,
+    PaymentFilter, PaymentRecord, PendingHook, Period, Plan, Profile, RevenueGroup, RevenueRow,
+    Target,
 };
 use chrono::{DateTime, Duration, Utc};
 use rusqlite::{Connection, Transaction};
@@ -19,6 +22,7 @@ macro_rules! try_sql {
 
 pub struct SqliteDb<'a> {
     trans: Transaction<'a>,
+    tracker: ChangeTracker,
 }
 
 impl<'a> SqliteDb<'a> {
@@ -27,35 +31,23 @@ impl<'a> SqliteDb<'a> {
     }
 
     pub fn new(conn: &'a mut Connection) -> Result<Self, String> {
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS clients (
-                name TEXT PRIMARY KEY,
-                expire_date TEXT NOT NULL,
-                info TEXT
-            )",
-            (),
-        )
-        .map_err(|e| format!("cannot create clients table: {}", e.to_string()))?;
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS payments (
-                client_name TEXT NOT NULL,
-                seller TEXT NOT NULL,
-                date TEXT NOT NULL,
-                money UNSIGNED INTEGER NOT NULL
-            )",
-            (),
-        )
-        .map_err(|e| format!("cannot create payments table: {}", e.to_string()))?;
+        migrations::migrate(conn)?;
         Ok(SqliteDb {
             trans: conn.transaction().map_err(|e| e.to_string())?,
+            tracker: ChangeTracker::default(),
         })
     }
 
-    fn get_payments(&self) -> Result<HashMap<String, Vec<Payment>>, String> {
-        let mut stmt = try_sql!(self
-            .trans
-            .prepare("SELECT client_name, seller, date, money FROM payments",));
-        let mut rows = try_sql!(stmt.query([]));
+    fn get_payments(
+        &self,
+        clients_clause: &str,
+        params: Vec<String>,
+    ) -> Result<HashMap<String, Vec<Payment>>, String> {
+        let mut stmt = try_sql!(self.trans.prepare(&format!(
+            "SELECT client_name, seller, date, money, days, plan FROM payments
+                WHERE client_name IN (SELECT name FROM clients {clients_clause}) ORDER BY rowid"
+        )));
+        let mut rows = try_sql!(stmt.query(rusqlite::params_from_iter(params)));
 
         let mut payments: HashMap<String, Vec<Payment>> = HashMap::new();
         while let Some(row) = try_sql!(rows.next()) {
@@ -66,6 +58,8 @@ impl<'a> SqliteDb<'a> {
                 seller: try_sql!(row.get(1)),
                 date: datetime_from_str(&date),
                 money: try_sql!(row.get(3)),
+                days: try_sql!(row.get(4)),
+                plan: try_sql!(row.get(5)),
             };
 
             payments
@@ -77,24 +71,75 @@ impl<'a> SqliteDb<'a> {
         Ok(payments)
     }
 
+    fn get_tags(
+        &self,
+        clients_clause: &str,
+        params: Vec<String>,
+    ) -> Result<HashMap<String, Vec<String>>, String> {
+        let mut stmt = try_sql!(self.trans.prepare(&format!(
+            "SELECT client_name, tag FROM client_tags
+                WHERE client_name IN (SELECT name FROM clients {clients_clause}) ORDER BY tag"
+        )));
+        let mut rows = try_sql!(stmt.query(rusqlite::params_from_iter(params)));
+
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        while let Some(row) = try_sql!(rows.next()) {
+            let client_name: String = try_sql!(row.get(0));
+            tags.entry(client_name)
+                .or_default()
+                .push(try_sql!(row.get(1)));
+        }
+        Ok(tags)
+    }
+
+    fn get_adjustments(
+        &self,
+        clients_clause: &str,
+        params: Vec<String>,
+    ) -> Result<HashMap<String, Vec<Adjustment>>, String> {
+        let mut stmt = try_sql!(self.trans.prepare(&format!(
+            "SELECT client_name, date, days, reason, operator FROM adjustments
+                WHERE client_name IN (SELECT name FROM clients {clients_clause}) ORDER BY rowid"
+        )));
+        let mut rows = try_sql!(stmt.query(rusqlite::params_from_iter(params)));
+
+        let mut adjustments: HashMap<String, Vec<Adjustment>> = HashMap::new();
+        while let Some(row) = try_sql!(rows.next()) {
+            let client_name: String = try_sql!(row.get(0));
+            let date: String = try_sql!(row.get(1));
+            adjustments
+                .entry(client_name)
+                .or_default()
+                .push(Adjustment {
+                    date: datetime_from_str(&date),
+                    days: try_sql!(row.get(2)),
+                    reason: try_sql!(row.get(3)),
+                    operator: try_sql!(row.get(4)),
+                });
+        }
+        Ok(adjustments)
+    }
+
     fn add_payment(
         &mut self,
         client_name: &str,
         seller: &str,
         date: &str,
         money: u32,
+        days: u32,
+        plan: Option<&str>,
     ) -> Result<(), String> {
         try_sql!(self.trans.execute(
-            "INSERT INTO payments (client_name, seller, date, money) VALUES (?, ?, ?, ?)",
-            (client_name, seller, date, money)
+            "INSERT INTO payments (client_name, seller, date, money, days, plan) VALUES (?, ?, ?, ?, ?, ?)",
+            (client_name, seller, date, money, days, plan)
         ));
         Ok(())
     }
 
     fn get_client_expire_date(&self, client_name: &str) -> Result<DateTime<Utc>, String> {
-        let mut stmt = try_sql!(self
-            .trans
-            .prepare("SELECT expire_date FROM clients WHERE name=? LIMIT 1"));
+        let mut stmt = try_sql!(self.trans.prepare(
+            "SELECT expire_date FROM clients WHERE name=? AND archived_at IS NULL LIMIT 1"
+        ));
         let mut rows = try_sql!(stmt.query([client_name]));
         let expire_date = match try_sql!(rows.next()) {
             Some(row) => {
@@ -110,6 +155,182 @@ impl<'a> SqliteDb<'a> {
         Ok(datetime_from_str(&expire_date))
     }
 
+    fn payment_filter_clause(filter: &PaymentFilter) -> (String, Vec<String>) {
+        let mut clause = "WHERE 1=1".to_string();
+        let mut params: Vec<String> = Vec::new();
+        if let Some(client_name) = &filter.client_name {
+            clause.push_str(" AND client_name=?");
+            params.push(client_name.clone());
+        }
+        if let Some(since) = &filter.since {
+            clause.push_str(" AND date >= ?");
+            params.push(datetime_to_str(since));
+        }
+        if let Some(until) = &filter.until {
+            clause.push_str(" AND date < ?");
+            params.push(datetime_to_str(until));
+        }
+        (clause, params)
+    }
+
+    fn select_clients(&self, clause: &str, params: Vec<String>) -> Result<Vec<Client>, String> {
+        let mut payments = self.get_payments(clause, params.clone())?;
+        let mut tags = self.get_tags(clause, params.clone())?;
+        let mut adjustments = self.get_adjustments(clause, params.clone())?;
+
+        let mut stmt = try_sql!(self.trans.prepare(&format!(
+            "SELECT name, expire_date, info, archived_at, created_at,
+                contact, telegram, email, notes, referred_by FROM clients {clause}"
+        )));
+        let mut rows = try_sql!(stmt.query(rusqlite::params_from_iter(params)));
+
+        let mut clients: Vec<Client> = Vec::new();
+        while let Some(row) = try_sql!(rows.next()) {
+            let client_name: String = try_sql!(row.get(0));
+            let expire_date: String = try_sql!(row.get(1));
+            let archived_at: Option<String> = try_sql!(row.get(3));
+            let created_at: Option<String> = try_sql!(row.get(4));
+
+            clients.push(Client {
+                payments: payments.remove(&client_name).unwrap(),
+                adjustments: adjustments.remove(&client_name).unwrap_or_default(),
+                tags: tags.remove(&client_name).unwrap_or_default(),
+                name: client_name,
+                expire_time: datetime_from_str(&expire_date),
+                info: try_sql!(row.get(2)),
+                archived_at: archived_at.as_deref().map(datetime_from_str),
+                created_at: created_at.as_deref().map(datetime_from_str),
+                profile: Profile {
+                    contact: try_sql!(row.get(5)),
+                    telegram: try_sql!(row.get(6)),
+                    email: try_sql!(row.get(7)),
+                    notes: try_sql!(row.get(8)),
+                    referred_by: try_sql!(row.get(9)),
+                },
+            });
+        }
+
+        Ok(clients)
+    }
+
+    fn insert_client(&mut self, client: &Client) -> Result<(), String> {
+        let rows_affected = try_sql!(self.trans.execute(
+            "INSERT OR IGNORE INTO clients (name, expire_date, info, archived_at, created_at,
+                contact, telegram, email, notes, referred_by)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            (
+                &client.name,
+                datetime_to_str(&client.expire_time),
+                &client.info,
+                client.archived_at.as_ref().map(datetime_to_str),
+                client.created_at.as_ref().map(datetime_to_str),
+                &client.profile.contact,
+                &client.profile.telegram,
+                &client.profile.email,
+                &client.profile.notes,
+                &client.profile.referred_by,
+            )
+        ));
+        if rows_affected == 0 {
+            return Err(format!("client '{}' already exists!", client.name));
+        }
+
+        for tag in &client.tags {
+            try_sql!(self.trans.execute(
+                "INSERT INTO client_tags (client_name, tag) VALUES (?, ?)",
+                (&client.name, tag)
+            ));
+        }
+        for adjustment in &client.adjustments {
+            self.add_adjustment(&client.name, adjustment)?;
+        }
+        for payment in &client.payments {
+            try_sql!(self.trans.execute(
+                "INSERT INTO payments (client_name, seller, date, money, days, plan) VALUES (?, ?, ?, ?, ?, ?)",
+                (
+                    &client.name,
+                    &payment.seller,
+                    datetime_to_str(&payment.date),
+                    payment.money,
+                    payment.days,
+                    &payment.plan
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    fn delete_client(&mut self, name: &str) -> Result<(), String> {
+        try_sql!(self
+            .trans
+            .execute("DELETE FROM clients WHERE name=?", (name,)));
+        try_sql!(self
+            .trans
+            .execute("DELETE FROM payments WHERE client_name=?", (name,)));
+        try_sql!(self
+            .trans
+            .execute("DELETE FROM client_tags WHERE client_name=?", (name,)));
+        try_sql!(self
+            .trans
+            .execute("DELETE FROM adjustments WHERE client_name=?", (name,)));
+        Ok(())
+    }
+
+    fn get_client(&self, name: &str) -> Result<Option<Client>, String> {
+        Ok(self
+            .select_clients("WHERE name=?", vec![name.to_string()])?
+            .pop())
+    }
+
+    fn track(&mut self, name: &str) -> Result<(), String> {
+        if !self.tracker.is_tracked(name) {
+            let before = self.get_client(name)?;
+            self.tracker.track(name, before);
+        }
+        Ok(())
+    }
+
+    fn track_where(&mut self, clause: &str, params: Vec<String>) -> Result<(), String> {
+        for client in self.select_clients(clause, params)? {
+            self.tracker.track(&client.name.clone(), Some(client));
+        }
+        Ok(())
+    }
+
+    fn add_adjustment(&mut self, client_name: &str, adjustment: &Adjustment) -> Result<(), String> {
+        try_sql!(self.trans.execute(
+            "INSERT INTO adjustments (client_name, date, days, reason, operator) VALUES (?, ?, ?, ?, ?)",
+            (
+                client_name,
+                datetime_to_str(&adjustment.date),
+                adjustment.days,
+                &adjustment.reason,
+                &adjustment.operator
+            )
+        ));
+        Ok(())
+    }
+
+    fn shift_where(
+        &mut self,
+        clause: &str,
+        params: Vec<String>,
+        days: i64,
+        reason: &str,
+    ) -> Result<(), String> {
+        for client in self.select_clients(clause, params)? {
+            let expire_date = client.expire_time + Duration::days(days);
+            let rows_affected = try_sql!(self.trans.execute(
+                "UPDATE clients SET expire_date=? WHERE name=?",
+                (datetime_to_str(&expire_date), &client.name)
+            ));
+            assert!(rows_affected > 0);
+            self.add_adjustment(&client.name, &Adjustment::new(days, reason))?;
+            self.tracker.track(&client.name.clone(), Some(client));
+        }
+        Ok(())
+    }
+
     fn get_last_payment_rowid(&self, client_name: &str) -> Result<u64, String> {
         let mut stmt = try_sql!(self
             .trans
@@ -130,6 +351,41 @@ impl<'a> SqliteDb<'a> {
     }
 }
 
+// condition on clients table that selects active clients of target
+fn target_clause(target: &Target) -> (String, Vec<String>) {
+    let (condition, params) = match target {
+        Target::All => ("1".to_string(), Vec::new()),
+        Target::MatchInfo(info) => ("info=?".to_string(), vec![info.clone()]),
+        Target::OnePerson(name) => ("name=?".to_string(), vec![name.clone()]),
+        Target::Names(names) => (
+            format!("name IN ({})", vec!["?"; names.len()].join(", ")),
+            names.clone(),
+        ),
+        Target::NameGlob(pattern) => ("name GLOB ?".to_string(), vec![pattern.clone()]),
+        Target::Seller(seller) => (
+            "(SELECT seller FROM payments WHERE client_name=clients.name ORDER BY rowid DESC LIMIT 1)=?"
+                .to_string(),
+            vec![seller.clone()],
+        ),
+        Target::ExpiringWithin(days) => {
+            let now_date = Utc::now();
+            (
+                "expire_date >= ? AND expire_date < ?".to_string(),
+                vec![
+                    datetime_to_str(&now_date),
+                    datetime_to_str(&(now_date + Duration::days((*days).into()))),
+                ],
+            )
+        }
+        Target::Tag(tag) => (
+            "name IN (SELECT client_name FROM client_tags WHERE tag=?)".to_string(),
+            vec![tag.clone()],
+        ),
+        Target::InfoContains(text) => ("instr(info, ?) > 0".to_string(), vec![text.clone()]),
+    };
+    (format!("archived_at IS NULL AND {condition}"), params)
+}
+
 impl Database for SqliteDb<'_> {
     fn add_client(
         &mut self,
@@ -137,22 +393,32 @@ impl Database for SqliteDb<'_> {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
         info: &str,
     ) -> Result<(), String> {
-        let new_client = Client::new(name, days, &seller, money, info);
+        self.track(name)?;
+        let new_client = Client::new(name, days, seller, money, plan, info);
         let expire_date = datetime_to_str(&new_client.expire_time);
-        let payment_date = datetime_to_str(&new_client.payments.get(0).unwrap().date);
+        let payment_date = datetime_to_str(&new_client.payments.first().unwrap().date);
 
         let rows_affected = try_sql!(self.trans.execute(
-            "INSERT OR IGNORE INTO clients (name, expire_date, info) VALUES (?, ?, ?)",
-            (name, expire_date.as_str(), info)
+            "INSERT OR IGNORE INTO clients (name, expire_date, info, created_at) VALUES (?, ?, ?, ?)",
+            (name, expire_date.as_str(), info, payment_date.as_str())
         ));
 
         if rows_affected == 0 {
+            if self
+                .get_client(name)?
+                .is_some_and(|client| client.archived_at.is_some())
+            {
+                return Err(format!(
+                    "client '{name}' is archived, restore or purge it first"
+                ));
+            }
             return Err(format!("client '{}' already exists!", name));
         }
 
-        self.add_payment(name, seller, &payment_date, money)?;
+        self.add_payment(name, seller, &payment_date, money, days, plan)?;
         Ok(())
     }
 
@@ -162,7 +428,9 @@ impl Database for SqliteDb<'_> {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
     ) -> Result<(), String> {
+        self.track(name)?;
         let mut expire_date = self.get_client_expire_date(name)?;
         let now_date = Utc::now();
         if now_date > expire_date {
@@ -175,33 +443,29 @@ impl Database for SqliteDb<'_> {
             (datetime_to_str(&expire_date), name)
         ));
         assert!(rows_affected > 0);
-        self.add_payment(name, seller, &datetime_to_str(&now_date), money)?;
+        self.add_payment(name, seller, &datetime_to_str(&now_date), money, days, plan)?;
         Ok(())
     }
 
-    fn renew_all_clients(&mut self, days: u32) -> Result<(), String> {
-        let mut stmt = try_sql!(self.trans.prepare("SELECT name, expire_date FROM clients"));
-        let rows = try_sql!(stmt.query_map([], |row| {
-            let name: String = row.get(0)?;
-            let expire_date: String = row.get(1)?;
-            Ok((name, datetime_from_str(&expire_date)))
-        }));
-
-        let now_date = Utc::now();
-        for row in rows {
-            let (name, mut expire_date) = try_sql!(row);
-            if expire_date < now_date {
-                continue;
-            }
-            expire_date += Duration::days(days.into());
+    fn renew_all_clients(&mut self, days: u32, target: Target, reason: &str) -> Result<(), String> {
+        let (condition, mut params) = target_clause(&target);
+        params.push(datetime_to_str(&Utc::now()));
+        self.shift_where(
+            &format!("WHERE {condition} AND expire_date >= ?"),
+            params,
+            days.into(),
+            reason,
+        )
+    }
 
-            let rows_affected = try_sql!(self.trans.execute(
-                "UPDATE clients SET expire_date=? WHERE name=?",
-                (datetime_to_str(&expire_date), name)
-            ));
-            assert!(rows_affected > 0);
-        }
-        Ok(())
+    fn shift_expire_times(
+        &mut self,
+        days: i64,
+        target: Target,
+        reason: &str,
+    ) -> Result<(), String> {
+        let (condition, params) = target_clause(&target);
+        self.shift_where(&format!("WHERE {condition}"), params, days, reason)
     }
 
     fn edit_client(
@@ -210,68 +474,169 @@ impl Database for SqliteDb<'_> {
         days: u32,
         seller: &str,
         money: u32,
+        plan: Option<&str>,
         info: &str,
     ) -> Result<(), String> {
-        let expire_date = datetime_to_str(&(Utc::now() + Duration::days(days.into())));
+        self.track(name)?;
+        let old_expire_date = self.get_client_expire_date(name)?;
+        let expire_date = Utc::now() + Duration::days(days.into());
         let rows_affected = try_sql!(self.trans.execute(
-            "UPDATE clients SET expire_date=?, info=? WHERE name=?",
-            (expire_date, info, name)
+            "UPDATE clients SET expire_date=?, info=? WHERE name=? AND archived_at IS NULL",
+            (datetime_to_str(&expire_date), info, name)
         ));
         assert!(rows_affected == 1);
+        // part of a day is lost when days left are shown, so it's not an adjustment
+        let adjusted_days = (expire_date - old_expire_date).num_days();
+        if adjusted_days != 0 {
+            self.add_adjustment(name, &Adjustment::new(adjusted_days, "edit"))?;
+        }
 
         let last_payment_rowid = self.get_last_payment_rowid(name)?;
         let rows_affected = try_sql!(self.trans.execute(
-            "UPDATE payments SET seller=?, money=? WHERE rowid=?",
-            (seller, money, last_payment_rowid)
+            "UPDATE payments SET seller=?, money=?, plan=? WHERE rowid=?",
+            (seller, money, plan, last_payment_rowid)
         ));
         assert!(rows_affected == 1);
         Ok(())
     }
 
     fn remove_client(&mut self, name: &str) -> Result<(), String> {
-        let rows_affected = try_sql!(self
-            .trans
-            .execute("DELETE FROM clients WHERE name=?", (name,)));
+        self.track(name)?;
+        let rows_affected = try_sql!(self.trans.execute(
+            "UPDATE clients SET archived_at=? WHERE name=? AND archived_at IS NULL",
+            (datetime_to_str(&Utc::now()), name)
+        ));
         if rows_affected == 0 {
             return Err(format!("client with name '{}' doesn't exists!", name));
         }
+        Ok(())
+    }
 
-        assert!(
-            try_sql!(self
-                .trans
-                .execute("DELETE FROM payments WHERE client_name=?", (name,)))
-                > 0
-        );
+    fn restore_client(&mut self, name: &str) -> Result<(), String> {
+        self.track(name)?;
+        let rows_affected = try_sql!(self.trans.execute(
+            "UPDATE clients SET archived_at=NULL WHERE name=? AND archived_at IS NOT NULL",
+            (name,)
+        ));
+        if rows_affected == 0 {
+            return Err(format!("there is no archived client with name '{}'", name));
+        }
         Ok(())
     }
 
+    fn purge_clients(&mut self, archived_before: DateTime<Utc>) -> Result<Vec<String>, String> {
+        let clients = self.select_clients(
+            "WHERE archived_at < ? ORDER BY name",
+            vec![datetime_to_str(&archived_before)],
+        )?;
+
+        let mut names = Vec::new();
+        for client in clients {
+            self.delete_client(&client.name)?;
+            names.push(client.name.clone());
+            self.tracker.track(&client.name.clone(), Some(client));
+        }
+        Ok(names)
+    }
+
     fn list_clients(&self) -> Result<Vec<Client>, String> {
-        let mut payments = self.get_payments()?;
+        self.select_clients("WHERE archived_at IS NULL", Vec::new())
+    }
 
-        let mut stmt = try_sql!(self
-            .trans
-            .prepare("SELECT name, expire_date, info FROM clients"));
-        let mut rows = try_sql!(stmt.query([]));
+    fn list_archived_clients(&self) -> Result<Vec<Client>, String> {
+        self.select_clients("WHERE archived_at IS NOT NULL", Vec::new())
+    }
 
-        let mut clients: Vec<Client> = Vec::new();
-        while let Some(row) = try_sql!(rows.next()) {
-            let client_name: String = try_sql!(row.get(0));
-            let expire_date: String = try_sql!(row.get(1));
+    fn query_clients(&self, query: &ClientQuery) -> Result<Vec<Client>, String> {
+        let last_payment = |column: &str| {
+            format!("(SELECT {column} FROM payments WHERE client_name=clients.name ORDER BY rowid DESC LIMIT 1)")
+        };
+        let mut clause = if query.archived {
+            "WHERE archived_at IS NOT NULL"
+        } else {
+            "WHERE archived_at IS NULL"
+        }
+        .to_string();
+        let mut params: Vec<String> = Vec::new();
 
-            clients.push(Client {
-                payments: payments.remove(&client_name).unwrap(),
-                name: client_name,
-                expire_time: datetime_from_str(&expire_date),
-                info: try_sql!(row.get(2)),
-            });
+        let now_date = Utc::now();
+        let mut expire_conditions: Vec<&str> = Vec::new();
+        if query.expired {
+            expire_conditions.push("expire_date < ?");
+            params.push(datetime_to_str(&now_date));
+        }
+        if let Some(days) = query.expiring_within {
+            expire_conditions.push("(expire_date >= ? AND expire_date < ?)");
+            params.push(datetime_to_str(&now_date));
+            params.push(datetime_to_str(&(now_date + Duration::days(days.into()))));
+        }
+        if !expire_conditions.is_empty() {
+            clause.push_str(&format!(" AND ({})", expire_conditions.join(" OR ")));
+        }
+        if let Some(seller) = &query.seller {
+            clause.push_str(&format!(" AND {}=?", last_payment("seller")));
+            params.push(seller.clone());
+        }
+        if let Some(text) = &query.info_contains {
+            clause.push_str(" AND instr(info, ?) > 0");
+            params.push(text.clone());
+        }
+        if let Some(pattern) = &query.name_glob {
+            clause.push_str(" AND name GLOB ?");
+            params.push(pattern.clone());
+        }
+        if let Some(tag) = &query.tag {
+            clause.push_str(" AND name IN (SELECT client_name FROM client_tags WHERE tag=?)");
+            params.push(tag.clone());
+        }
+        if let Some(text) = &query.search {
+            // fields are joined by a newline so a match can't span two of them
+            let fields = [
+                "name",
+                "info",
+                "contact",
+                "telegram",
+                "email",
+                "notes",
+                "referred_by",
+            ]
+            .map(|column| format!("ifnull({column}, '')"))
+            .join(" || char(10) || ");
+            clause.push_str(&format!(" AND instr(lower({fields}), lower(?)) > 0"));
+            params.push(text.clone());
         }
 
-        Ok(clients)
+        let order = match query.sort {
+            ClientSort::Name => "name".to_string(),
+            ClientSort::Expire => "expire_date DESC, name".to_string(),
+            ClientSort::LastPayment => format!("{} DESC, name", last_payment("date")),
+            ClientSort::TotalPaid => {
+                "(SELECT SUM(money) FROM payments WHERE client_name=clients.name) DESC, name"
+                    .to_string()
+            }
+        };
+        clause.push_str(&format!(" ORDER BY {order}"));
+        if let Some(limit) = query.limit {
+            clause.push_str(&format!(" LIMIT {limit}"));
+        }
+
+        self.select_clients(&clause, params)
+    }
+
+    fn import_client(&mut self, client: &Client) -> Result<(), String> {
+        self.track(&client.name)?;
+        self.delete_client(&client.name)?;
+        self.insert_client(client)
     }
 
     fn rename_client(&mut self, old_name: &str, new_name: &str) -> Result<(), String> {
+        if self.get_client(new_name)?.is_some() {
+            return Err(format!("client '{}' already exists!", new_name));
+        }
+        self.track(old_name)?;
+        self.tracker.rename(old_name, new_name);
         let rows_affected = try_sql!(self.trans.execute(
-            "UPDATE clients SET name=? WHERE name=?",
+            "UPDATE clients SET name=? WHERE name=? AND archived_at IS NULL",
             (new_name, old_name)
         ));
         if rows_affected == 0 {
@@ -283,21 +648,50 @@ impl Database for SqliteDb<'_> {
             (new_name, old_name)
         ));
         assert!(rows_affected > 0);
+        try_sql!(self.trans.execute(
+            "UPDATE client_tags SET client_name=? WHERE client_name=?",
+            (new_name, old_name)
+        ));
+        try_sql!(self.trans.execute(
+            "UPDATE adjustments SET client_name=? WHERE client_name=?",
+            (new_name, old_name)
+        ));
+        Ok(())
+    }
+
+    fn set_client_profile(&mut self, name: &str, profile: &Profile) -> Result<(), String> {
+        self.track(name)?;
+        let rows_affected = try_sql!(self.trans.execute(
+            "UPDATE clients SET contact=?, telegram=?, email=?, notes=?, referred_by=?
+                WHERE name=? AND archived_at IS NULL",
+            (
+                &profile.contact,
+                &profile.telegram,
+                &profile.email,
+                &profile.notes,
+                &profile.referred_by,
+                name,
+            )
+        ));
+        if rows_affected == 0 {
+            return Err(format!("client with name '{}' doesn't exists!", name));
+        }
         Ok(())
     }
 
     fn set_client_info(&mut self, target: Target, info: &str) -> Result<(), String> {
-        let stmt = match target {
-            Target::All => self.trans.execute("UPDATE clients SET info=?", (info,)),
-            Target::MatchInfo(ref old_info) => self
-                .trans
-                .execute("UPDATE clients SET info=? WHERE info=?", (info, old_info)),
-            Target::OnePerson(ref name) => self
-                .trans
-                .execute("UPDATE clients SET info=? WHERE name=?", (info, name)),
-        };
+        let (condition, params) = target_clause(&target);
+        match &target {
+            Target::OnePerson(name) => self.track(name)?,
+            _ => self.track_where(&format!("WHERE {condition}"), params.clone())?,
+        }
 
-        let rows_affected = try_sql!(stmt);
+        let mut all_params = vec![info.to_string()];
+        all_params.extend(params);
+        let rows_affected = try_sql!(self.trans.execute(
+            &format!("UPDATE clients SET info=? WHERE {condition}"),
+            rusqlite::params_from_iter(all_params)
+        ));
         if let Target::OnePerson(name) = target {
             if rows_affected == 0 {
                 return Err(format!("client with name '{}' doesn't exists!", name));
@@ -307,10 +701,44 @@ impl Database for SqliteDb<'_> {
         Ok(())
     }
 
+    fn add_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String> {
+        if self
+            .get_client(name)?
+            .is_none_or(|client| client.archived_at.is_some())
+        {
+            return Err(format!("client with name '{}' doesn't exists!", name));
+        }
+        self.track(name)?;
+        for tag in tags {
+            try_sql!(self.trans.execute(
+                "INSERT OR IGNORE INTO client_tags (client_name, tag) VALUES (?, ?)",
+                (name, tag)
+            ));
+        }
+        Ok(())
+    }
+
+    fn remove_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String> {
+        if self
+            .get_client(name)?
+            .is_none_or(|client| client.archived_at.is_some())
+        {
+            return Err(format!("client with name '{}' doesn't exists!", name));
+        }
+        self.track(name)?;
+        for tag in tags {
+            try_sql!(self.trans.execute(
+                "DELETE FROM client_tags WHERE client_name=? AND tag=?",
+                (name, tag)
+            ));
+        }
+        Ok(())
+    }
+
     fn get_client_info(&self, name: &str) -> Result<String, String> {
         let mut stmt = try_sql!(self
             .trans
-            .prepare("SELECT info FROM clients WHERE name=? LIMIT 1"));
+            .prepare("SELECT info FROM clients WHERE name=? AND archived_at IS NULL LIMIT 1"));
         let mut rows = try_sql!(stmt.query([name]));
 
         let maybe_row = try_sql!(rows.next());
@@ -320,8 +748,365 @@ impl Database for SqliteDb<'_> {
         }
     }
 
-    fn commit(self) -> Result<(), String> {
+    fn list_payments(&self, filter: &PaymentFilter) -> Result<Vec<PaymentRecord>, String> {
+        let (clause, params) = Self::payment_filter_clause(filter);
+        let query = format!(
+            "SELECT client_name, seller, date, money, days, plan FROM payments {clause} ORDER BY date, rowid"
+        );
+
+        let mut stmt = try_sql!(self.trans.prepare(&query));
+        let rows = try_sql!(stmt.query_map(rusqlite::params_from_iter(params), |row| {
+            let date: String = row.get(2)?;
+            Ok(PaymentRecord {
+                client_name: row.get(0)?,
+                payment: Payment {
+                    seller: row.get(1)?,
+                    date: datetime_from_str(&date),
+                    money: row.get(3)?,
+                    days: row.get(4)?,
+                    plan: row.get(5)?,
+                },
+            })
+        }));
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(try_sql!(row));
+        }
+        Ok(records)
+    }
+
+    fn revenue(
+        &self,
+        period: Period,
+        group: RevenueGroup,
+        filter: &PaymentFilter,
+    ) -> Result<Vec<RevenueRow>, String> {
+        let (clause, mut params) = Self::payment_filter_clause(filter);
+        let group_column = match group {
+            RevenueGroup::Seller => "seller",
+            RevenueGroup::Plan => "COALESCE(plan, '-')",
+        };
+        let query = format!(
+            "SELECT strftime(?, date) AS period, {group_column} AS grp, SUM(money), COUNT(*)
+                FROM payments {clause} GROUP BY period, grp ORDER BY period, grp"
+        );
+        params.insert(0, period.format().to_string());
+
+        let mut stmt = try_sql!(self.trans.prepare(&query));
+        let rows = try_sql!(stmt.query_map(rusqlite::params_from_iter(params), |row| {
+            Ok(RevenueRow {
+                period: row.get(0)?,
+                group: row.get(1)?,
+                total: row.get(2)?,
+                count: row.get(3)?,
+            })
+        }));
+
+        let mut revenue = Vec::new();
+        for row in rows {
+            revenue.push(try_sql!(row));
+        }
+        Ok(revenue)
+    }
+
+    fn add_seller(&mut self, name: &str) -> Result<(), String> {
+        let rows_affected = try_sql!(self
+            .trans
+            .execute("INSERT OR IGNORE INTO sellers (name) VALUES (?)", (name,)));
+        if rows_affected == 0 {
+            return Err(format!("seller '{}' already exists!", name));
+        }
+        Ok(())
+    }
+
+    fn remove_seller(&mut self, name: &str) -> Result<(), String> {
+        let rows_affected = try_sql!(self
+            .trans
+            .execute("DELETE FROM sellers WHERE name=?", (name,)));
+        if rows_affected == 0 {
+            return Err(format!("seller with name '{}' doesn't exists!", name));
+        }
+        Ok(())
+    }
+
+    fn rename_seller(&mut self, old_name: &str, new_name: &str) -> Result<(), String> {
+        self.track_where(
+            "WHERE name IN (SELECT client_name FROM payments WHERE seller=?)",
+            vec![old_name.to_string()],
+        )?;
+        if self.list_sellers()?.iter().any(|seller| seller == new_name) {
+            return Err(format!("seller '{}' already exists!", new_name));
+        }
+
+        let rows_affected = try_sql!(self.trans.execute(
+            "UPDATE sellers SET name=? WHERE name=?",
+            (new_name, old_name)
+        ));
+        if rows_affected == 0 {
+            return Err(format!("seller with name '{}' doesn't exists!", old_name));
+        }
+
+        try_sql!(self.trans.execute(
+            "UPDATE payments SET seller=? WHERE seller=?",
+            (new_name, old_name)
+        ));
+        Ok(())
+    }
+
+    fn list_sellers(&self) -> Result<Vec<String>, String> {
+        let mut stmt = try_sql!(self
+            .trans
+            .prepare("SELECT name FROM sellers ORDER BY rowid"));
+        let rows = try_sql!(stmt.query_map([], |row| row.get(0)));
+
+        let mut sellers = Vec::new();
+        for row in rows {
+            sellers.push(try_sql!(row));
+        }
+        Ok(sellers)
+    }
+
+    fn add_plan(&mut self, plan: &Plan) -> Result<(), String> {
+        let rows_affected = try_sql!(self.trans.execute(
+            "INSERT OR IGNORE INTO plans (name, days, money) VALUES (?, ?, ?)",
+            (&plan.name, plan.days, plan.money)
+        ));
+        if rows_affected == 0 {
+            return Err(format!("plan '{}' already exists!", plan.name));
+        }
+        Ok(())
+    }
+
+    fn remove_plan(&mut self, name: &str) -> Result<(), String> {
+        let rows_affected = try_sql!(self
+            .trans
+            .execute("DELETE FROM plans WHERE name=?", (name,)));
+        if rows_affected == 0 {
+            return Err(format!("plan with name '{}' doesn't exists!", name));
+        }
+        Ok(())
+    }
+
+    fn list_plans(&self) -> Result<Vec<Plan>, String> {
+        let mut stmt = try_sql!(self
+            .trans
+            .prepare("SELECT name, days, money FROM plans ORDER BY days, name"));
+        let rows = try_sql!(stmt.query_map([], |row| {
+            Ok(Plan {
+                name: row.get(0)?,
+                days: row.get(1)?,
+                money: row.get(2)?,
+            })
+        }));
+
+        let mut plans = Vec::new();
+        for row in rows {
+            plans.push(try_sql!(row));
+        }
+        Ok(plans)
+    }
+
+    fn add_pending_hook(&mut self, hook: &PendingHook) -> Result<(), String> {
+        try_sql!(self.trans.execute(
+            "INSERT INTO pending_hooks
+                (date, operation, event, script, arguments, client, payload, attempts, error)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            (
+                datetime_to_str(&hook.date),
+                &hook.operation,
+                &hook.event,
+                &hook.script,
+                serde_json::to_string(&hook.arguments).unwrap(),
+                &hook.client,
+                &hook.payload,
+                hook.attempts,
+                &hook.error,
+            )
+        ));
+        Ok(())
+    }
+
+    fn update_pending_hook(&mut self, hook: &PendingHook) -> Result<(), String> {
+        let updated_count = try_sql!(self.trans.execute(
+            "UPDATE pending_hooks SET attempts=?, error=? WHERE id=?",
+            (hook.attempts, &hook.error, hook.id)
+        ));
+        if updated_count == 0 {
+            return Err(format!("pending hook {} doesn't exists!", hook.id));
+        }
+        Ok(())
+    }
+
+    fn remove_pending_hook(&mut self, id: u64) -> Result<(), String> {
+        let deleted_count = try_sql!(self
+            .trans
+            .execute("DELETE FROM pending_hooks WHERE id=?", (id,)));
+        if deleted_count == 0 {
+            return Err(format!("pending hook {id} doesn't exists!"));
+        }
+        Ok(())
+    }
+
+    fn list_pending_hooks(&self) -> Result<Vec<PendingHook>, String> {
+        let mut stmt = try_sql!(self.trans.prepare(
+            "SELECT id, date, operation, event, script, arguments, client, payload, attempts, error
+                FROM pending_hooks ORDER BY id"
+        ));
+        let mut rows = try_sql!(stmt.query(()));
+
+        let mut hooks = Vec::new();
+        while let Some(row) = try_sql!(rows.next()) {
+            let date: String = try_sql!(row.get(1));
+            let arguments: String = try_sql!(row.get(5));
+            hooks.push(PendingHook {
+                id: try_sql!(row.get(0)),
+                date: datetime_from_str(&date),
+                operation: try_sql!(row.get(2)),
+                event: try_sql!(row.get(3)),
+                script: try_sql!(row.get(4)),
+                arguments: serde_json::from_str(&arguments)
+                    .map_err(|e| format!("cannot parse hook arguments: {e}"))?,
+                client: try_sql!(row.get(6)),
+                payload: try_sql!(row.get(7)),
+                attempts: try_sql!(row.get(8)),
+                error: try_sql!(row.get(9)),
+            });
+        }
+        Ok(hooks)
+    }
+
+    fn add_notification(&mut self, notification: &Notification) -> Result<(), String> {
+        try_sql!(self.trans.execute(
+            "INSERT INTO notifications (client_name, threshold, expire_date, sent_at)
+                VALUES (?, ?, ?, ?)",
+            (
+                &notification.client_name,
+                notification.threshold,
+                datetime_to_str(&notification.expire_time),
+                datetime_to_str(&notification.sent_at),
+            )
+        ));
+        Ok(())
+    }
+
+    fn is_notified(
+        &self,
+        client_name: &str,
+        threshold: u32,
+        expire_time: &DateTime<Utc>,
+    ) -> Result<bool, String> {
+        let count: u32 = try_sql!(self.trans.query_row(
+            "SELECT COUNT(*) FROM notifications
+                WHERE client_name=? AND threshold=? AND expire_date=?",
+            (client_name, threshold, datetime_to_str(expire_time)),
+            |row| row.get(0)
+        ));
+        Ok(count > 0)
+    }
+
+    fn begin_operation(&mut self, operation: &str, arguments: &[String]) {
+        self.tracker.begin(operation, arguments);
+    }
+
+    fn list_audit_entries(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String> {
+        let mut query =
+            "SELECT id, date, user, operation, arguments, changes, reverts FROM audit_log WHERE 1=1"
+                .to_string();
+        let mut params: Vec<String> = Vec::new();
+        if let Some(operation) = &filter.operation {
+            query.push_str(" AND operation=?");
+            params.push(operation.clone());
+        }
+        if let Some(name) = &filter.client_name {
+            params.push(name.clone());
+            let index = params.len();
+            query.push_str(&format!(
+                " AND EXISTS (SELECT 1 FROM json_each(changes)
+                    WHERE json_extract(value, '$.before.name')=?{index}
+                        OR json_extract(value, '$.after.name')=?{index})"
+            ));
+        }
+        query.push_str(" ORDER BY id DESC");
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {limit}"));
+        }
+
+        let mut stmt = try_sql!(self.trans.prepare(&query));
+        let mut rows = try_sql!(stmt.query(rusqlite::params_from_iter(params)));
+
+        let mut entries = Vec::new();
+        while let Some(row) = try_sql!(rows.next()) {
+            let date: String = try_sql!(row.get(1));
+            let arguments: String = try_sql!(row.get(4));
+            let changes: String = try_sql!(row.get(5));
+            entries.push(AuditEntry {
+                id: try_sql!(row.get(0)),
+                date: datetime_from_str(&date),
+                user: try_sql!(row.get(2)),
+                operation: try_sql!(row.get(3)),
+                arguments: serde_json::from_str(&arguments)
+                    .map_err(|e| format!("cannot parse audit arguments: {e}"))?,
+                changes: serde_json::from_str(&changes)
+                    .map_err(|e| format!("cannot parse audit changes: {e}"))?,
+                reverts: try_sql!(row.get(6)),
+            });
+        }
+
+        entries.reverse();
+        Ok(entries)
+    }
+
+    fn pending_changes(&self) -> Result<Vec<ClientChange>, String> {
+        self.tracker.changes(|name| self.get_client(name))
+    }
+
+    fn revert_changes(&mut self, entry: &AuditEntry) -> Result<(), String> {
+        // removing everything first so swapped names don't collide
+        for after in entry
+            .changes
+            .iter()
+            .filter_map(|change| change.after.as_ref())
+        {
+            self.track(&after.name)?;
+            self.delete_client(&after.name)?;
+        }
+        for before in entry
+            .changes
+            .iter()
+            .filter_map(|change| change.before.as_ref())
+        {
+            self.track(&before.name)?;
+            self.insert_client(before)?;
+        }
+
+        self.tracker.reverts(entry.id);
+        Ok(())
+    }
+
+    fn commit(mut self) -> Result<(), String> {
+        let tracker = std::mem::take(&mut self.tracker);
+        if let Some(entry) = tracker.into_entry(|name| self.get_client(name))? {
+            try_sql!(self.trans.execute(
+                "INSERT INTO audit_log (date, user, operation, arguments, changes, reverts)
+                    VALUES (?, ?, ?, ?, ?, ?)",
+                (
+                    datetime_to_str(&entry.date),
+                    entry.user,
+                    entry.operation,
+                    serde_json::to_string(&entry.arguments).unwrap(),
+                    serde_json::to_string(&entry.changes).unwrap(),
+                    entry.reverts,
+                )
+            ));
+        }
+
         try_sql!(self.trans.commit());
         Ok(())
     }
+
+    fn rollback(self) -> Result<(), String> {
+        try_sql!(self.trans.rollback());
+        Ok(())
+    }
 }
@@ -0,0 +1,338 @@
+use crate::{
+    db::{
+        datetime_serializer::{datetime_to_str, parse},
+        jsondb::JsonDb,
+        sqlitedb::SqliteDb,
+        Client, Database, Payment, Plan, Profile,
+    },
+    report::escape_delimited,
+};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::{env, fs, path::Path, process};
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Json,
+    // one row per payment, columns of client are repeated in each of them,
+    // sellers and plans aren't kept so importing it leaves them as they are
+    Csv,
+    Sqlite,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &Path) -> Result<FileFormat, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(FileFormat::Json),
+            Some("csv") => Ok(FileFormat::Csv),
+            Some("db" | "sqlite") => Ok(FileFormat::Sqlite),
+            _ => Err(format!(
+                "cannot guess format of '{}' from its extension, pass --format",
+                path.to_str().unwrap()
+            )),
+        }
+    }
+}
+
+// everything that is moved between databases, audit log and hooks are left behind
+pub struct Dump {
+    // archived clients included
+    pub clients: Vec<Client>,
+    pub sellers: Vec<String>,
+    pub plans: Vec<Plan>,
+}
+
+const CSV_HEADERS: [&str; 17] = [
+    "name",
+    "expire_time",
+    "archived_at",
+    "created_at",
+    "info",
+    "contact",
+    "telegram",
+    "email",
+    "notes",
+    "referred_by",
+    "tags",
+    // json array, they have more fields than a column can hold
+    "adjustments",
+    "seller",
+    "date",
+    "money",
+    "days",
+    "plan",
+];
+
+pub fn read<T: Database>(db: &T) -> Result<Dump, String> {
+    let mut clients = db.list_clients()?;
+    clients.extend(db.list_archived_clients()?);
+    Ok(Dump {
+        clients,
+        sellers: db.list_sellers()?,
+        plans: db.list_plans()?,
+    })
+}
+
+// makes sellers of db same as the dump, adds missing plans and writes every client as is
+pub fn write<T: Database>(db: &mut T, dump: &Dump) -> Result<(), String> {
+    for seller in db.list_sellers()? {
+        if !dump.sellers.contains(&seller) {
+            db.remove_seller(&seller)?;
+        }
+    }
+    add_missing(db, dump)?;
+    for client in &dump.clients {
+        db.import_client(client)?;
+    }
+    Ok(())
+}
+
+// sellers and plans that db doesn't have yet
+pub fn add_missing<T: Database>(db: &mut T, dump: &Dump) -> Result<(), String> {
+    let sellers = db.list_sellers()?;
+    for seller in &dump.sellers {
+        if !sellers.contains(seller) {
+            db.add_seller(seller)?;
+        }
+    }
+    let plans = db.list_plans()?;
+    for plan in &dump.plans {
+        if !plans.iter().any(|exist_plan| exist_plan.name == plan.name) {
+            db.add_plan(plan)?;
+        }
+    }
+    Ok(())
+}
+
+// existing fields are kept, payments, adjustments and tags it doesn't have are added
+pub fn merge(existing: &Client, imported: &Client) -> Client {
+    let mut client = existing.clone();
+    client.expire_time = existing.expire_time.max(imported.expire_time);
+    for payment in &imported.payments {
+        if !client.payments.contains(payment) {
+            client.payments.push(payment.clone());
+        }
+    }
+    client.payments.sort_by_key(|payment| payment.date);
+    for adjustment in &imported.adjustments {
+        if !client.adjustments.contains(adjustment) {
+            client.adjustments.push(adjustment.clone());
+        }
+    }
+    client.adjustments.sort_by_key(|adjustment| adjustment.date);
+    client.tags.extend(imported.tags.iter().cloned());
+    client.tags.sort();
+    client.tags.dedup();
+
+    client.info = existing.info.clone().or(imported.info.clone());
+    client.created_at = match (existing.created_at, imported.created_at) {
+        (Some(existing_date), Some(imported_date)) => Some(existing_date.min(imported_date)),
+        (existing_date, imported_date) => existing_date.or(imported_date),
+    };
+    let (profile, other) = (&mut client.profile, &imported.profile);
+    for (field, other_field) in [
+        (&mut profile.contact, &other.contact),
+        (&mut profile.telegram, &other.telegram),
+        (&mut profile.email, &other.email),
+        (&mut profile.notes, &other.notes),
+        (&mut profile.referred_by, &other.referred_by),
+    ] {
+        if field.is_none() {
+            field.clone_from(other_field);
+        }
+    }
+    client
+}
+
+pub fn export_file(dump: &Dump, format: FileFormat, path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!(
+            "'{}' already exists, won't overwrite it",
+            path.to_str().unwrap()
+        ));
+    }
+
+    match format {
+        FileFormat::Json => {
+            let mut db = JsonDb::new(path.to_path_buf())?;
+            write(&mut db, dump)?;
+            db.commit()
+        }
+        FileFormat::Sqlite => {
+            let mut conn = SqliteDb::create_connection(path.to_path_buf())?;
+            let mut db = SqliteDb::new(&mut conn)?;
+            write(&mut db, dump)?;
+            db.commit()
+        }
+        FileFormat::Csv => fs::write(path, to_csv(&dump.clients))
+            .map_err(|e| format!("cannot write to file '{}': {e}", path.to_str().unwrap())),
+    }
+}
+
+pub fn import_file(path: &Path, format: FileFormat) -> Result<Dump, String> {
+    if !path.is_file() {
+        return Err(format!("file '{}' doesn't exist", path.to_str().unwrap()));
+    }
+
+    match format {
+        FileFormat::Json => read(&JsonDb::open_read_only(path.to_path_buf())?),
+        // opening applies migrations, so a copy is opened to leave the file untouched
+        FileFormat::Sqlite => {
+            let copy_path = env::temp_dir().join(format!("manjaliof-import-{}.db", process::id()));
+            fs::copy(path, &copy_path).map_err(|e| format!("cannot copy database: {e}"))?;
+            let dump = SqliteDb::create_connection(copy_path.clone())
+                .and_then(|mut conn| read(&SqliteDb::new(&mut conn)?));
+            fs::remove_file(&copy_path).map_err(|e| format!("cannot remove copy: {e}"))?;
+            dump
+        }
+        FileFormat::Csv => {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("cannot open file '{}': {e}", path.to_str().unwrap()))?;
+            from_csv(&content)
+        }
+    }
+}
+
+fn to_csv(clients: &[Client]) -> String {
+    let escape = |column: &str| escape_delimited(column, ',');
+    let mut lines = vec![CSV_HEADERS.join(",")];
+    for client in clients {
+        let format_date = |date: &Option<DateTime<Utc>>| date.as_ref().map(datetime_to_str);
+        for payment in &client.payments {
+            let columns = [
+                Some(client.name.clone()),
+                Some(datetime_to_str(&client.expire_time)),
+                format_date(&client.archived_at),
+                format_date(&client.created_at),
+                client.info.clone(),
+                client.profile.contact.clone(),
+                client.profile.telegram.clone(),
+                client.profile.email.clone(),
+                client.profile.notes.clone(),
+                client.profile.referred_by.clone(),
+                Some(client.tags.join(" ")),
+                Some(client.adjustments.clone())
+                    .filter(|adjustments| !adjustments.is_empty())
+                    .map(|adjustments| serde_json::to_string(&adjustments).unwrap()),
+                Some(payment.seller.clone()),
+                Some(datetime_to_str(&payment.date)),
+                Some(payment.money.to_string()),
+                payment.days.map(|days| days.to_string()),
+                payment.plan.clone(),
+            ];
+            let columns: Vec<String> = columns
+                .iter()
+                .map(|column| escape(column.as_deref().unwrap_or_default()))
+                .collect();
+            lines.push(columns.join(","));
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn from_csv(content: &str) -> Result<Dump, String> {
+    let mut records = parse_csv(content)?.into_iter();
+    let headers = records.next().unwrap_or_default();
+    if headers != CSV_HEADERS {
+        return Err(format!("csv headers must be: {}", CSV_HEADERS.join(",")));
+    }
+
+    let mut clients: Vec<Client> = Vec::new();
+    for (index, record) in records.enumerate() {
+        let line_error = |e: String| format!("cannot parse row {}: {e}", index + 1);
+        if record.len() != CSV_HEADERS.len() {
+            return Err(line_error(format!(
+                "expected {} columns but got {}",
+                CSV_HEADERS.len(),
+                record.len()
+            )));
+        }
+        let optional = |column: usize| Some(record[column].clone()).filter(|text| !text.is_empty());
+        let date = |column: usize| {
+            parse(&record[column]).map_err(|e| format!("invalid {}: {e}", CSV_HEADERS[column]))
+        };
+        let optional_date = |column: usize| optional(column).map(|_| date(column)).transpose();
+        let number = |column: usize| {
+            record[column]
+                .parse::<u32>()
+                .map_err(|_| format!("{} must be a number", CSV_HEADERS[column]))
+        };
+
+        let payment = Payment {
+            seller: record[12].clone(),
+            date: date(13).map_err(line_error)?,
+            money: number(14).map_err(line_error)?,
+            days: optional(15)
+                .map(|_| number(15))
+                .transpose()
+                .map_err(line_error)?,
+            plan: optional(16),
+        };
+
+        // client columns are repeated in every row, the first row of client is used
+        match clients.iter_mut().find(|client| client.name == record[0]) {
+            Some(client) => client.payments.push(payment),
+            None => clients.push(Client {
+                name: record[0].clone(),
+                expire_time: date(1).map_err(line_error)?,
+                payments: vec![payment],
+                adjustments: optional(11)
+                    .map(|adjustments| serde_json::from_str(&adjustments))
+                    .transpose()
+                    .map_err(|e| line_error(format!("invalid adjustments: {e}")))?
+                    .unwrap_or_default(),
+                info: optional(4),
+                archived_at: optional_date(2).map_err(line_error)?,
+                created_at: optional_date(3).map_err(line_error)?,
+                profile: Profile {
+                    contact: optional(5),
+                    telegram: optional(6),
+                    email: optional(7),
+                    notes: optional(8),
+                    referred_by: optional(9),
+                },
+                tags: record[10].split_whitespace().map(str::to_string).collect(),
+            }),
+        }
+    }
+
+    Ok(Dump {
+        clients,
+        sellers: Vec::new(),
+        plans: Vec::new(),
+    })
+}
+
+// quoted columns may contain commas, newlines and doubled quotes
+fn parse_csv(content: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut column = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                column.push('"');
+                chars.next();
+            }
+            ('"', _) => in_quotes = !in_quotes,
+            (',', false) => record.push(std::mem::take(&mut column)),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut column));
+                records.push(std::mem::take(&mut record));
+            }
+            ('\r', false) => {}
+            _ => column.push(ch),
+        }
+    }
+    if in_quotes {
+        return Err("csv ends inside a quoted column".to_string());
+    }
+    if !column.is_empty() || !record.is_empty() {
+        record.push(column);
+        records.push(record);
+    }
+    Ok(records)
+}
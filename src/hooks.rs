@@ -0,0 +1,473 @@
+use crate::{
+    config::HooksConfig,
+    db::{audit::ClientChange, Client, Payment, PendingHook},
+};
+use chrono::Utc;
+use dialoguer::console::style;
+use serde::Serialize;
+use std::{
+    cell::RefCell,
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+// events that had post scripts before hooks got payloads, their script must exist
+const REQUIRED_EVENTS: [&str; 4] = ["add", "renew", "delete", "rename"];
+
+// those scripts take a single client, so a bulk operation runs them once per client
+pub fn takes_one_client(event: &str) -> bool {
+    REQUIRED_EVENTS.contains(&event)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Phase {
+    // runs before commit, a failing pre hook vetoes the operation
+    Pre,
+    Post,
+}
+
+impl Phase {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Phase::Pre => "pre",
+            Phase::Post => "post",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HookChange {
+    client: String,
+    before: Option<Client>,
+    after: Option<Client>,
+
+    // payment made or edited by the operation
+    payment: Option<Payment>,
+    seller: Option<String>,
+}
+
+impl HookChange {
+    fn new(change: ClientChange) -> HookChange {
+        let client = change
+            .after
+            .as_ref()
+            .or(change.before.as_ref())
+            .map_or(String::new(), |client| client.name.clone());
+        let before_payment = change
+            .before
+            .as_ref()
+            .and_then(|client| client.payments.last());
+        let payment = change
+            .after
+            .as_ref()
+            .and_then(|client| client.payments.last())
+            .filter(|payment| before_payment != Some(payment))
+            .cloned();
+
+        HookChange {
+            client,
+            seller: payment.as_ref().map(|payment| payment.seller.clone()),
+            payment,
+            before: change.before,
+            after: change.after,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    operation: &'a str,
+    arguments: &'a [String],
+    changes: Vec<HookChange>,
+}
+
+// everything needed to run a script once more
+struct Invocation<'a> {
+    script: &'a str,
+    operation: &'a str,
+    event: &'a str,
+    phase: Phase,
+    client: &'a str,
+    args: &'a [String],
+    payload: &'a str,
+}
+
+pub struct Hooks {
+    dir: PathBuf,
+    logs_dir: PathBuf,
+    config: HooksConfig,
+    skip_post: bool,
+    // a dry run is rolled back, so no hook may act on it
+    dry_run: bool,
+    operation: String,
+    arguments: Vec<String>,
+    // post hooks that failed while `queue_failed` is set
+    failed: RefCell<Vec<PendingHook>>,
+}
+
+impl Hooks {
+    pub fn new(
+        dir: PathBuf,
+        logs_dir: PathBuf,
+        config: &HooksConfig,
+        skip_post: bool,
+        dry_run: bool,
+        operation: &str,
+        arguments: &[String],
+    ) -> Hooks {
+        Hooks {
+            dir,
+            logs_dir,
+            config: config.clone(),
+            skip_post,
+            dry_run,
+            operation: operation.to_string(),
+            arguments: arguments.to_vec(),
+            failed: RefCell::new(Vec::new()),
+        }
+    }
+
+    // scripts get `args` as argv, the payload on stdin and context in MANJALIOF_* variables
+    pub fn run(
+        &self,
+        phase: Phase,
+        event: &str,
+        args: &[String],
+        changes: Vec<ClientChange>,
+    ) -> Result<(), String> {
+        if self.is_skipped(phase) {
+            return Ok(());
+        }
+
+        let scripts = self.scripts(phase, event);
+        if scripts.is_empty() {
+            return Ok(());
+        }
+
+        let client = match changes.as_slice() {
+            [change] => HookChange::new(change.clone()).client,
+            _ => String::new(),
+        };
+        let payload = Payload {
+            operation: &self.operation,
+            arguments: &self.arguments,
+            changes: changes.into_iter().map(HookChange::new).collect(),
+        };
+        let payload = serde_json::to_string(&payload).unwrap();
+
+        for script in scripts {
+            let invocation = Invocation {
+                script: &script,
+                operation: &self.operation,
+                event,
+                phase,
+                client: &client,
+                args,
+                payload: &payload,
+            };
+            match self.run_with_retries(&invocation) {
+                Ok(()) => {}
+                Err(error) if phase == Phase::Post && self.config.queue_failed => {
+                    println!(
+                        "{}",
+                        style(format!("'{script}' is queued to retry later: {error}")).yellow()
+                    );
+                    self.failed.borrow_mut().push(PendingHook {
+                        id: 0,
+                        date: Utc::now(),
+                        operation: self.operation.clone(),
+                        event: event.to_string(),
+                        script,
+                        arguments: args.to_vec(),
+                        client: client.clone(),
+                        payload: payload.clone(),
+                        attempts: self.config.retries + 1,
+                        error,
+                    });
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_scripts(&self, phase: Phase, event: &str) -> bool {
+        !self.is_skipped(phase) && !self.scripts(phase, event).is_empty()
+    }
+
+    pub fn is_skipped(&self, phase: Phase) -> bool {
+        self.dry_run || (phase == Phase::Post && self.skip_post)
+    }
+
+    // runs a queued hook again, `hook` is updated with the result
+    pub fn retry(&self, hook: &mut PendingHook) -> Result<(), String> {
+        let result = self.run_with_retries(&Invocation {
+            script: &hook.script,
+            operation: &hook.operation,
+            event: &hook.event,
+            phase: Phase::Post,
+            client: &hook.client,
+            args: &hook.arguments,
+            payload: &hook.payload,
+        });
+        hook.attempts += self.config.retries + 1;
+        if let Err(error) = &result {
+            hook.error = error.clone();
+        }
+        result
+    }
+
+    // name and content of the most recent logs, oldest first
+    pub fn recent_logs(
+        &self,
+        limit: usize,
+        script: Option<&str>,
+    ) -> Result<Vec<(String, String)>, String> {
+        let entries = match fs::read_dir(&self.logs_dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(Vec::new()),
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_str().unwrap().to_string())
+            .filter(|name| {
+                script.is_none_or(|script| {
+                    name.ends_with(&format!("-{}.log", script.replace('/', "_")))
+                })
+            })
+            .collect();
+        names.sort();
+
+        let skip_count = names.len().saturating_sub(limit);
+        let mut logs = Vec::new();
+        for name in names.into_iter().skip(skip_count) {
+            let path = self.logs_dir.join(&name);
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("cannot read log '{}': {e}", path.to_str().unwrap()))?;
+            logs.push((name, content));
+        }
+        Ok(logs)
+    }
+
+    // hooks that are failed and should be kept to retry
+    pub fn take_failed(&self) -> Vec<PendingHook> {
+        self.failed.take()
+    }
+
+    // `<event>` and then every file of `<event>.d/` in name order, pre hooks are `pre-<event>`,
+    // paths are relative to hooks folder
+    fn scripts(&self, phase: Phase, event: &str) -> Vec<String> {
+        let name = match phase {
+            Phase::Pre => format!("pre-{event}"),
+            Phase::Post => event.to_string(),
+        };
+
+        let drop_in_dir = format!("{name}.d");
+        let mut drop_ins: Vec<String> = match fs::read_dir(self.dir.join(&drop_in_dir)) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| format!("{drop_in_dir}/{}", entry.file_name().to_str().unwrap()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        drop_ins.sort();
+
+        let is_required = phase == Phase::Post && REQUIRED_EVENTS.contains(&event);
+        let mut scripts = Vec::new();
+        // missing required script fails when it's run, like it always did
+        if self.dir.join(&name).is_file() || (is_required && drop_ins.is_empty()) {
+            scripts.push(name);
+        }
+        scripts.append(&mut drop_ins);
+        scripts
+    }
+
+    fn run_with_retries(&self, invocation: &Invocation) -> Result<(), String> {
+        let retries = match invocation.phase {
+            Phase::Pre => 0,
+            Phase::Post => self.config.retries,
+        };
+        let mut delay = Duration::from_secs(self.config.retry_delay_secs);
+
+        let mut attempt = 0;
+        loop {
+            match self.run_script(invocation) {
+                Err(error) if attempt < retries => {
+                    println!(
+                        "{}",
+                        style(format!(
+                            "'{}' failed, retrying in {}s: {error}",
+                            invocation.script,
+                            delay.as_secs()
+                        ))
+                        .yellow()
+                    );
+                    thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn run_script(&self, invocation: &Invocation) -> Result<(), String> {
+        let log = Arc::new(Mutex::new(self.create_log(invocation)?));
+        let script_path = self.dir.join(invocation.script);
+        let mut child = Command::new(&script_path)
+            .args(invocation.args)
+            .env("MANJALIOF_OP", invocation.operation)
+            .env("MANJALIOF_EVENT", invocation.event)
+            .env("MANJALIOF_PHASE", invocation.phase.as_str())
+            .env("MANJALIOF_CLIENT", invocation.client)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| {
+                let error = format!(
+                    "couldn't run post script '{}': {error}",
+                    script_path.to_str().unwrap(),
+                );
+                write_log(&log, &format!("# {error}"));
+                error
+            })?;
+
+        // pipes are handled by other threads so a script that doesn't read stdin or writes a lot
+        // can't block us, it's fine for the script to close stdin early
+        let mut stdin = child.stdin.take().unwrap();
+        let payload = invocation.payload.to_string();
+        let writer = thread::spawn(move || {
+            let _ = stdin.write_all(payload.as_bytes());
+        });
+        let prefix = format!("[{}]", invocation.script);
+        let stdout = stream_output(child.stdout.take().unwrap(), &prefix, &log, false);
+        let stderr = stream_output(child.stderr.take().unwrap(), &prefix, &log, true);
+
+        let timeout = self.config.timeout(invocation.event);
+        let start_time = Instant::now();
+        let status = loop {
+            let status = child
+                .try_wait()
+                .map_err(|error| format!("couldn't wait for '{}': {error}", invocation.script))?;
+            if let Some(status) = status {
+                break status;
+            }
+            if timeout != 0 && start_time.elapsed() >= Duration::from_secs(timeout) {
+                let _ = child.kill();
+                let _ = child.wait();
+                let error = format!(
+                    "'{}' is killed after running for {timeout}s",
+                    invocation.script
+                );
+                write_log(&log, &format!("# {error}"));
+                return Err(error);
+            }
+            thread::sleep(Duration::from_millis(20));
+        };
+        writer.join().unwrap();
+        stdout.join().unwrap();
+        let stderr = stderr.join().unwrap();
+        write_log(&log, &format!("# {status}"));
+
+        if !status.success() {
+            let stderr = stderr.strip_suffix('\n').unwrap_or(&stderr);
+            return Err(match invocation.phase {
+                Phase::Pre => {
+                    format!("operation is vetoed by '{}': {stderr}", invocation.script)
+                }
+                Phase::Post => format!("post script exited due to a failure: {stderr}"),
+            });
+        }
+        Ok(())
+    }
+
+    // every run gets its own file, named so they sort by time
+    fn create_log(&self, invocation: &Invocation) -> Result<fs::File, String> {
+        let error_msg = |path: &Path, e: std::io::Error| {
+            format!("cannot create log '{}': {e}", path.to_str().unwrap())
+        };
+        fs::create_dir_all(&self.logs_dir).map_err(|e| error_msg(&self.logs_dir, e))?;
+        self.rotate_logs();
+
+        let file_name = format!(
+            "{}-{}.log",
+            Utc::now().format("%Y%m%d-%H%M%S%.6f"),
+            invocation.script.replace('/', "_")
+        );
+        let path = self.logs_dir.join(file_name);
+        let mut file = fs::File::create(&path).map_err(|e| error_msg(&path, e))?;
+        let header = format!(
+            "# {} {}\n# operation: {}, client: {}\n",
+            invocation.script,
+            invocation.args.join(" "),
+            invocation.operation,
+            invocation.client
+        );
+        file.write_all(header.as_bytes())
+            .map_err(|e| error_msg(&path, e))?;
+        Ok(file)
+    }
+
+    // leaves room for the log that's about to be created, a failed removal must not fail the hook
+    fn rotate_logs(&self) {
+        let Ok(entries) = fs::read_dir(&self.logs_dir) else {
+            return;
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .filter(|name| name.ends_with(".log"))
+            .collect();
+        names.sort();
+
+        let old_count = names
+            .len()
+            .saturating_sub(self.config.keep_logs.saturating_sub(1));
+        for name in &names[..old_count] {
+            let _ = fs::remove_file(self.logs_dir.join(name));
+        }
+    }
+}
+
+// a failed write only loses the log, it must not fail the hook
+fn write_log(log: &Mutex<fs::File>, line: &str) {
+    let _ = writeln!(log.lock().unwrap(), "{line}");
+}
+
+// prints every line as soon as it comes and writes it to the log, returns whole output
+fn stream_output<R: Read + Send + 'static>(
+    pipe: R,
+    prefix: &str,
+    log: &Arc<Mutex<fs::File>>,
+    is_stderr: bool,
+) -> thread::JoinHandle<String> {
+    let prefix = style(prefix.to_string()).dim();
+    let log = Arc::clone(log);
+    thread::spawn(move || {
+        let mut output = String::new();
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        while reader.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+            let text = String::from_utf8_lossy(&line);
+            let text = text.strip_suffix('\n').unwrap_or(&text);
+            if is_stderr {
+                eprintln!("{prefix} {text}");
+                write_log(&log, &format!("err| {text}"));
+            } else {
+                println!("{prefix} {text}");
+                write_log(&log, &format!("out| {text}"));
+            }
+            output.push_str(text);
+            output.push('\n');
+            line.clear();
+        }
+        output
+    })
+}
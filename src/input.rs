@@ -1,8 +1,23 @@
 pub mod validators;
 
-use dialoguer::{console::style, theme, Input, Select};
+use crate::db::Plan;
+use dialoguer::{
+    console::{style, Term},
+    theme, Confirm, Input, Select,
+};
 
-pub const SELLERS: [&'static str; 2] = ["arian", "pouya"];
+// fails instead of blocking when there is nobody to answer
+pub fn confirm(prompt: &str) -> Result<bool, String> {
+    if !Term::stderr().is_term() {
+        return Err("cannot ask for confirmation without a terminal, pass --yes".to_string());
+    }
+
+    Confirm::with_theme(&get_theme())
+        .with_prompt(prompt)
+        .default(false)
+        .interact()
+        .map_err(|e| e.to_string())
+}
 
 pub fn get_client_name() -> String {
     Input::with_theme(&get_theme())
@@ -18,30 +33,69 @@ pub fn get_client_new_name() -> String {
         .unwrap()
 }
 
-pub fn get_seller() -> String {
+pub fn get_seller(sellers: &[String]) -> String {
     let reffer_index: usize = Select::with_theme(&get_theme())
         .with_prompt("who gets money")
-        .items(&SELLERS)
+        .items(sellers)
+        .interact()
+        .unwrap();
+    sellers.get(reffer_index).unwrap().to_string()
+}
+
+// last item is for typing days and money by hand
+pub fn get_plan(plans: &[Plan]) -> Option<Plan> {
+    let mut items: Vec<String> = plans
+        .iter()
+        .map(|plan| format!("{} ({} days, {})", plan.name, plan.days, plan.money))
+        .collect();
+    items.push("custom".to_string());
+
+    let plan_index: usize = Select::with_theme(&get_theme())
+        .with_prompt("which plan")
+        .default(0)
+        .items(&items)
         .interact()
         .unwrap();
-    SELLERS.get(reffer_index).unwrap().to_string()
+    plans.get(plan_index).cloned()
 }
 
-pub fn get_new_seller(old_seller: &str) -> String {
-    let old_seller = SELLERS.iter().position(|&x| x == old_seller).unwrap();
+pub fn get_plan_name() -> String {
+    Input::with_theme(&get_theme())
+        .with_prompt("plan name")
+        .interact_text()
+        .unwrap()
+}
+
+pub fn get_new_seller(sellers: &[String], old_seller: &str) -> String {
+    // old seller may have been removed from roster since the payment
+    let old_seller = sellers.iter().position(|x| x == old_seller).unwrap_or(0);
     let reffer_index: usize = Select::with_theme(&get_theme())
         .with_prompt("who gets money")
         .default(old_seller)
-        .items(&SELLERS)
+        .items(sellers)
         .interact()
         .unwrap();
-    SELLERS.get(reffer_index).unwrap().to_string()
+    sellers.get(reffer_index).unwrap().to_string()
+}
+
+pub fn get_seller_name() -> String {
+    Input::with_theme(&get_theme())
+        .with_prompt("seller name")
+        .interact_text()
+        .unwrap()
+}
+
+pub fn get_seller_new_name() -> String {
+    Input::with_theme(&get_theme())
+        .with_prompt("seller new name")
+        .interact_text()
+        .unwrap()
 }
 
-pub fn get_money_amount() -> u32 {
+pub fn get_money_amount(default_money: u32) -> u32 {
     Input::with_theme(&get_theme())
         .with_prompt("money money")
-        .default("60".into())
+        .default(default_money.to_string())
         .validate_with(validators::NumberValidator {})
         .interact_text()
         .unwrap()
@@ -60,10 +114,10 @@ pub fn get_new_money_amount(old_money_amount: u32) -> u32 {
         .unwrap()
 }
 
-pub fn get_days() -> u32 {
+pub fn get_days(default_days: u32) -> u32 {
     Input::with_theme(&get_theme())
         .with_prompt("how many days")
-        .default("30".into())
+        .default(default_days.to_string())
         .validate_with(validators::NumberValidator {})
         .interact_text()
         .unwrap()
@@ -91,10 +145,22 @@ pub fn get_info(last_info: Option<&str>) -> String {
         .unwrap()
 }
 
+// empty answer leaves the field unset
+pub fn get_profile_field(prompt: &str, last_value: Option<&str>) -> Option<String> {
+    let value: String = Input::with_theme(&get_theme())
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .with_initial_text(last_value.unwrap_or(""))
+        .interact_text()
+        .unwrap();
+    Some(value).filter(|value| !value.is_empty())
+}
+
 fn get_theme() -> impl theme::Theme {
-    let mut theme = theme::ColorfulTheme::default();
-    theme.success_prefix = style("✓".to_string()).for_stderr().green();
-    theme.checked_item_prefix = style("✓".to_string()).for_stderr().green();
-    theme.unchecked_item_prefix = style("✓".to_string()).for_stderr().black();
-    theme
+    theme::ColorfulTheme {
+        success_prefix: style("✓".to_string()).for_stderr().green(),
+        checked_item_prefix: style("✓".to_string()).for_stderr().green(),
+        unchecked_item_prefix: style("✓".to_string()).for_stderr().black(),
+        ..Default::default()
+    }
 }
@@ -1,10 +1,5 @@
 use dialoguer::Validator;
 
-use super::SELLERS;
-
-const MAX_NAME_LENGTH: usize = 35;
-const MAX_INFO_LENGTH: usize = 64;
-
 pub struct NumberValidator {}
 impl Validator<String> for NumberValidator {
     type Err = String;
@@ -17,8 +12,8 @@ impl Validator<String> for NumberValidator {
     }
 }
 
-pub fn validate_name(name: &String) -> Result<(), String> {
-    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+pub fn validate_name(name: &str, max_length: usize) -> Result<(), String> {
+    if name.is_empty() || name.len() > max_length {
         return Err("cannot validate name: text is too short or too long".to_string());
     }
 
@@ -32,21 +27,39 @@ pub fn validate_name(name: &String) -> Result<(), String> {
     Ok(())
 }
 
-pub fn validate_seller(seller: &String) -> Result<(), String> {
-    if !SELLERS.contains(&seller.as_str()) {
+// same rules as names, the error tells which tag it is since a client can have many
+pub fn validate_tag(tag: &str, max_length: usize) -> Result<(), String> {
+    validate_name(tag, max_length).map_err(|e| {
+        e.replacen(
+            "cannot validate name",
+            &format!("cannot validate tag '{tag}'"),
+            1,
+        )
+    })
+}
+
+pub fn validate_seller(seller: &str, sellers: &[String]) -> Result<(), String> {
+    if !sellers.iter().any(|x| x == seller) {
         return Err(format!(
             "cannot validate seller: only this sellers are valid: {}",
-            SELLERS.join(", ")
+            sellers.join(", ")
         ));
     }
     Ok(())
 }
 
-pub fn validate_info(info: &String) -> Result<(), String> {
-    if info.is_empty() || info.len() > MAX_INFO_LENGTH {
-        return Err(format!(
-            "cannot validate info: text is too short or too long"
-        ));
+pub fn validate_info(info: &str, max_length: usize) -> Result<(), String> {
+    if info.is_empty() || info.len() > max_length {
+        return Err("cannot validate info: text is too short or too long".to_string());
     }
     Ok(())
 }
+
+pub fn validate_email(email: &str) -> Result<(), String> {
+    match email.split_once('@') {
+        Some((user, domain)) if !user.is_empty() && domain.contains('.') => Ok(()),
+        _ => Err(format!(
+            "cannot validate email: '{email}' is not an email address"
+        )),
+    }
+}
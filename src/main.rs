@@ -1,24 +1,47 @@
 mod cli;
+mod config;
 mod db;
+mod hooks;
 mod input;
 mod report;
 
-use chrono::Utc;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
 use clap::{CommandFactory, Parser};
 use cli::{
-    AddArgs, Cli, Commands, EditArgs, ListArgs, RemoveArgs, RenameArgs, RenewAllArgs, RenewArgs,
-    SetInfoArgs,
+    AddArgs, AuditArgs, BackupArgs, BackupCommands, BulkEditArgs, CleanupArgs, Cli, Commands,
+    ConfigArgs, ConfigCommands, ConfirmArgs, DbArgs, DbCommands, EditArgs, ExportArgs, HistoryArgs,
+    HooksArgs, HooksCommands, ImportArgs, LedgerArgs, ListArgs, ListSort, NotifyArgs, OutputArgs,
+    PlanArgs, PlanCommands, ProfileArgs, PurgeArgs, RemoveArgs, RenameArgs, RenewAllArgs,
+    RenewArgs, ReportArgs, ReportCommands, RestoreArgs, RevenueArgs, RevenueGroupBy, RevenuePeriod,
+    SellerArgs, SellerCommands, SetInfoArgs, SettleArgs, TagArgs, TagCommands, TargetArgs,
+    UndoArgs,
 };
-use db::{sqlitedb::SqliteDb, Database, Target};
-use dialoguer::console::style;
-use report::{client_report, Report};
-use std::{env, io, path::Path, process, process::ExitCode};
+use config::{Backend, BackupConfig, BackupSchedule, Config};
+use db::{
+    audit::{AuditFilter, ClientChange},
+    backup,
+    jsondb::JsonDb,
+    migrations,
+    sqlitedb::SqliteDb,
+    transfer::{self, FileFormat},
+    Client, ClientQuery, ClientSort, Database, Notification, PaymentFilter, Period, Plan, Profile,
+    RevenueGroup, Target,
+};
+use dialoguer::console::{self, style};
+use hooks::{Hooks, Phase};
+use report::{audit_report, client_report, payment_report, Format, Report};
+use rusqlite::Connection;
+use std::{env, fs, io, io::Write, path::Path, process::ExitCode};
 
 type PostScriptArgs = Option<Vec<String>>;
 
 const DATA_PATH_ENV_NAME: &str = "MANJALIOF_DATA";
 const DB_FILE_NAME: &str = "data.db";
+const JSON_DB_FILE_NAME: &str = "data.json";
+const CONFIG_FILE_NAME: &str = "config.toml";
 const POST_SCRIPTS_FOLDER_NAME: &str = "post_scripts";
+const LOGS_FOLDER_NAME: &str = "logs";
+const BACKUPS_FOLDER_NAME: &str = "backups";
 
 fn main() -> ExitCode {
     match try_main() {
@@ -34,264 +57,1857 @@ fn main() -> ExitCode {
 fn try_main() -> Result<(), String> {
     let cli = Cli::parse();
 
-    let db_path = Path::new(&get_data_path()?).join(DB_FILE_NAME);
-    let mut conn = SqliteDb::create_connection(db_path)?;
-    let mut db = SqliteDb::new(&mut conn)?;
+    let data_path = get_data_path()?;
+    let config_path = match &cli.config {
+        Some(config_path) if !config_path.is_file() => {
+            return Err(format!(
+                "config file '{}' doesn't exist",
+                config_path.to_str().unwrap()
+            ))
+        }
+        Some(config_path) => config_path.clone(),
+        None => Path::new(&data_path).join(CONFIG_FILE_NAME),
+    };
+    let config = Config::load(&config_path)?;
+    if let Commands::Config(args) = cli.command {
+        return manage_config(&config, &config_path, args);
+    }
 
-    let command_result = try_run_command(cli, &mut db);
-    if !command_result.is_err() {
+    match cli.backend.unwrap_or(config.database.backend) {
+        Backend::Sqlite => {
+            let db_path = Path::new(&data_path).join(DB_FILE_NAME);
+            let mut conn = SqliteDb::create_connection(db_path)?;
+            let backups_path = Path::new(&data_path).join(BACKUPS_FOLDER_NAME);
+            if let Commands::Backup(args) = cli.command {
+                return manage_backups(&mut conn, &backups_path, args, &config.backup);
+            }
+            // opening database applies migrations so it must be handled before that
+            if let Commands::Db(args) = cli.command {
+                if matches!(&args.command, DbCommands::Migrate(args) if !args.dry_run) {
+                    auto_backup(&conn, &backups_path, &config.backup, "migrate")?;
+                }
+                return manage_db(&mut conn, args);
+            }
+            // a dry run is rolled back, so there is nothing to back up for
+            let operation =
+                get_command_operation(&cli.command).filter(|_| !cli.command.is_dry_run());
+            if let Some(operation) = operation {
+                auto_backup(&conn, &backups_path, &config.backup, operation)?;
+            }
+            let db = SqliteDb::new(&mut conn)?;
+            run_and_commit(cli, db, &config)
+        }
+        Backend::Json => {
+            if let Commands::Db(_) = cli.command {
+                return Err("db commands are only available with sqlite backend".to_string());
+            }
+            if let Commands::Backup(_) = cli.command {
+                return Err("backup commands are only available with sqlite backend".to_string());
+            }
+            let db = JsonDb::new(Path::new(&data_path).join(JSON_DB_FILE_NAME))?;
+            run_and_commit(cli, db, &config)
+        }
+    }
+}
+
+fn run_and_commit<T: Database>(cli: Cli, mut db: T, config: &Config) -> Result<(), String> {
+    let dry_run = cli.command.is_dry_run();
+    let stopped_error = try_run_command(cli, &mut db, config)?;
+    if dry_run {
+        db.rollback()?;
+        println!("{}", style("dry run, nothing is saved").yellow());
+    } else {
         db.commit()
             .map_err(|e| format!("CRITICAL ERROR: cannot commit changes: {e}"))?;
     }
 
-    command_result
+    stopped_error.map_or(Ok(()), Err)
 }
 
-fn try_run_command<T: Database>(cli: Cli, db: &mut T) -> Result<(), String> {
-    let post_script_name = get_command_post_script(&cli.command, cli.skip_post_script);
+// an error returned in Ok is reported after committing, it's for commands that stop midway
+// after some of their changes are already acted on outside of database
+fn try_run_command<T: Database>(
+    cli: Cli,
+    db: &mut T,
+    config: &Config,
+) -> Result<Option<String>, String> {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let operation = get_command_operation(&cli.command);
+    if let Some(operation) = operation {
+        db.begin_operation(operation, &arguments);
+    }
+    let data_path = get_data_path()?;
+    if cli.skip_post_script {
+        println!("{}", style("skipping post script!").yellow());
+    }
+    let hooks = Hooks::new(
+        Path::new(&data_path).join(POST_SCRIPTS_FOLDER_NAME),
+        Path::new(&data_path).join(LOGS_FOLDER_NAME),
+        &config.hooks,
+        cli.skip_post_script,
+        cli.command.is_dry_run(),
+        // notify is run often and changes no client, so it's not recorded as an operation
+        match cli.command {
+            Commands::Notify(_) => "notify",
+            _ => operation.unwrap_or_default(),
+        },
+        &arguments,
+    );
+
+    let mut stopped_error = None;
     let post_script_arg = match cli.command {
-        Commands::Add(args) => add_client(db, args)?,
-        Commands::Renew(args) => renew_client(db, args)?,
-        Commands::RenewAll(args) => renew_all_clients(db, args)?,
-        Commands::Edit(args) => edit_client(db, args)?,
-        Commands::Remove(args) => remove_client(db, args)?,
-        Commands::List(args) => list_clients(db, args)?,
-        Commands::Rename(args) => rename_client(db, args)?,
+        Commands::Add(args) => add_client(db, args, config)?,
+        Commands::Renew(args) => renew_client(db, args, config)?,
+        Commands::RenewAll(args) => renew_all_clients(db, args, config)?,
+        Commands::Edit(args) => edit_client(db, args, config)?,
+        Commands::BulkEdit(args) => bulk_edit_clients(db, args, config)?,
+        Commands::Remove(args) => remove_client(db, args, config)?,
+        Commands::Restore(args) => restore_client(db, args)?,
+        Commands::Purge(args) => purge_clients(db, args)?,
+        Commands::List(args) => list_clients(db, args, config)?,
+        Commands::Rename(args) => rename_client(db, args, config)?,
         Commands::SetInfo(args) => set_client_info(db, args)?,
-        Commands::Cleanup => cleanup(db)?,
+        Commands::Cleanup(args) => cleanup(db, args, config, &hooks, &mut stopped_error)?,
+        Commands::History(args) => show_history(db, args)?,
+        Commands::Ledger(args) => show_ledger(db, args)?,
+        Commands::Report(args) => show_report(db, args)?,
+        Commands::Seller(args) => manage_sellers(db, args, config)?,
+        Commands::Plan(args) => manage_plans(db, args, config)?,
+        Commands::Audit(args) => show_audit(db, args)?,
+        Commands::Undo(args) => undo(db, args, &hooks)?,
+        Commands::Tag(args) => manage_tags(db, args, config)?,
+        Commands::Notify(args) => notify(db, args, config, &hooks)?,
+        Commands::Export(args) => export(db, args)?,
+        Commands::Import(args) => import(db, args, config)?,
+        Commands::Hooks(args) => manage_hooks(db, args, &hooks)?,
+        Commands::Db(_) => unreachable!("db commands are handled before opening database"),
+        Commands::Backup(_) => unreachable!("backup commands are handled before opening database"),
+        Commands::Config(_) => unreachable!("config commands are handled before opening database"),
         Commands::Version => version(),
         Commands::GenerateBashCompletion => generate_bash_completion(),
     };
 
-    if let (Some(name), Some(args)) = (post_script_name, post_script_arg) {
-        run_post_script(name, args)?;
+    // cleanup and undo run their hooks while they work
+    if let Some(operation) = operation.filter(|operation| !["cleanup", "undo"].contains(operation))
+    {
+        let changes = db.pending_changes()?;
+        let hook_args = post_script_arg
+            .clone()
+            .unwrap_or_else(|| get_changed_names(&changes));
+        let event = get_hook_event(operation);
+        // a failed pre hook returns before commit, so nothing is changed
+        hooks.run(Phase::Pre, event, &hook_args, changes.clone())?;
+        if post_script_arg.is_none() && hooks::takes_one_client(event) {
+            for change in changes {
+                let names = get_changed_names(std::slice::from_ref(&change));
+                hooks.run(Phase::Post, event, &names, vec![change])?;
+            }
+        } else {
+            hooks.run(Phase::Post, event, &hook_args, changes)?;
+        }
+    }
+    // committed with the operation so failed hooks are never lost
+    for hook in hooks.take_failed() {
+        db.add_pending_hook(&hook)?;
     }
 
-    Ok(())
+    Ok(stopped_error)
 }
 
-fn add_client<T: Database>(db: &mut T, args: AddArgs) -> Result<PostScriptArgs, String> {
+fn add_client<T: Database>(
+    db: &mut T,
+    args: AddArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
     let name = args.name.unwrap_or_else(input::get_client_name);
-    let days = args.days.unwrap_or_else(input::get_days);
-    let seller = args.seller.unwrap_or_else(input::get_seller);
-    let money = args.money.unwrap_or_else(input::get_money_amount);
+    let (plan, days, money) = get_payment_terms(db, args.plan, args.days, args.money, config)?;
+    let sellers = get_sellers(db)?;
+    let seller = args.seller.unwrap_or_else(|| input::get_seller(&sellers));
+    // profile is asked along with info
+    let ask_profile = args.info.is_none();
     let info = args.info.unwrap_or_else(|| input::get_info(None));
+    let profile = get_profile(args.profile, &Profile::default(), ask_profile, config)?;
 
-    input::validators::validate_name(&name)?;
-    input::validators::validate_seller(&seller)?;
-    input::validators::validate_info(&info)?;
+    input::validators::validate_name(&name, config.limits.max_name_length)?;
+    input::validators::validate_seller(&seller, &sellers)?;
+    input::validators::validate_info(&info, config.limits.max_info_length)?;
 
-    db.add_client(&name, days, &seller, money, &info)?;
+    db.add_client(&name, days, &seller, money, plan.as_deref(), &info)?;
+    if profile != Profile::default() {
+        db.set_client_profile(&name, &profile)?;
+    }
     Ok(Some(vec![name]))
 }
 
-fn renew_client<T: Database>(db: &mut T, args: RenewArgs) -> Result<PostScriptArgs, String> {
+fn renew_client<T: Database>(
+    db: &mut T,
+    args: RenewArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
     let name = args.name.unwrap_or_else(input::get_client_name);
-    let days = args.days.unwrap_or_else(input::get_days);
-    let seller = args.seller.unwrap_or_else(input::get_seller);
-    let money = args.money.unwrap_or_else(input::get_money_amount);
-    let mut info = args.info.unwrap_or(String::new());
+    let (plan, days, money) = get_payment_terms(db, args.plan, args.days, args.money, config)?;
+    let sellers = get_sellers(db)?;
+    let seller = args.seller.unwrap_or_else(|| input::get_seller(&sellers));
+    let mut info = args.info.unwrap_or_default();
 
     if info.is_empty() {
         let last_info = db.get_client_info(&name)?;
         info = input::get_info(Some(&last_info));
     }
 
-    input::validators::validate_name(&name)?;
-    input::validators::validate_seller(&seller)?;
-    input::validators::validate_info(&info)?;
+    input::validators::validate_name(&name, config.limits.max_name_length)?;
+    input::validators::validate_seller(&seller, &sellers)?;
+    input::validators::validate_info(&info, config.limits.max_info_length)?;
 
-    db.renew_client(&name, days, &seller, money)?;
+    db.renew_client(&name, days, &seller, money, plan.as_deref())?;
     db.set_client_info(Target::OnePerson(name.clone()), &info)?;
+    if !args.profile.is_empty() {
+        let last_profile = find_client(db, &name)?.profile;
+        let profile = get_profile(args.profile, &last_profile, false, config)?;
+        db.set_client_profile(&name, &profile)?;
+    }
     Ok(Some(vec![name]))
 }
 
 fn renew_all_clients<T: Database>(
     db: &mut T,
     args: RenewAllArgs,
+    config: &Config,
 ) -> Result<PostScriptArgs, String> {
-    println!(
-        "{}",
-        style("you are renewing all clients that are not expired!").yellow()
-    );
-    let days = args.days.unwrap_or_else(input::get_days);
-    db.renew_all_clients(days)?;
+    check_conflicts(&args.target.given_flags())?;
+    let target = get_target(&args.target).unwrap_or(Target::All);
+    if let Target::All = target {
+        println!(
+            "{}",
+            style("you are renewing all clients that are not expired!").yellow()
+        );
+    }
+    let now_time = Utc::now();
+    let clients: Vec<Client> = select_targets(db, &target)?
+        .into_iter()
+        .filter(|client| client.expire_time >= now_time)
+        .collect();
+    if clients.is_empty() {
+        return Err("no client matches the target".to_string());
+    }
+
+    let days = args
+        .days
+        .unwrap_or_else(|| input::get_days(config.defaults.days));
+    let reason = args.reason.as_deref().unwrap_or("renew-all");
+    db.renew_all_clients(days, Target::Names(get_names(&clients)), reason)?;
+    confirm_changes(&db.pending_changes()?, "renew", &args.target.confirm)?;
     Ok(None)
 }
 
-fn edit_client<T: Database>(db: &mut T, args: EditArgs) -> Result<PostScriptArgs, String> {
+fn edit_client<T: Database>(
+    db: &mut T,
+    args: EditArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
     let name = args.name.unwrap_or_else(input::get_client_name);
-    let client = db
-        .list_clients()?
-        .into_iter()
-        .find(|client| client.name == name)
-        .ok_or(format!("client with name '{name}' doesn't exists!"))?;
+    let client = find_client(db, &name)?;
 
     let now_time = Utc::now();
     let days_remain = (client.expire_time - now_time).num_days();
     if days_remain < 0 {
         return Err("cannot edit an expired client".to_string());
     }
+    let plan = match &args.plan {
+        Some(plan_name) => Some(find_plan(db, plan_name)?),
+        None => None,
+    };
     let days = args
         .days
+        .or(plan.as_ref().map(|plan| plan.days))
         .unwrap_or_else(|| input::get_new_days(days_remain.try_into().unwrap()));
 
     let last_payment = client.payments.last().unwrap();
+    let sellers = get_sellers(db)?;
     let seller = args
         .seller
-        .unwrap_or_else(|| input::get_new_seller(&last_payment.seller));
+        .unwrap_or_else(|| input::get_new_seller(&sellers, &last_payment.seller));
     let money = args
         .money
+        .or(plan.as_ref().map(|plan| plan.money))
         .unwrap_or_else(|| input::get_new_money_amount(last_payment.money));
+    let plan = plan.map(|plan| plan.name).or(last_payment.plan.clone());
 
     let last_info = client.info.unwrap_or("".to_string());
+    let ask_profile = args.info.is_none();
     let info = args
         .info
         .unwrap_or_else(|| input::get_info(Some(&last_info)));
+    let profile = get_profile(args.profile, &client.profile, ask_profile, config)?;
 
-    input::validators::validate_seller(&seller)?;
-    input::validators::validate_info(&info)?;
+    input::validators::validate_seller(&seller, &sellers)?;
+    input::validators::validate_info(&info, config.limits.max_info_length)?;
 
-    db.edit_client(&name, days, &seller, money, &info)?;
+    db.edit_client(&name, days, &seller, money, plan.as_deref(), &info)?;
+    if profile != client.profile {
+        db.set_client_profile(&name, &profile)?;
+    }
     Ok(None)
 }
 
-fn remove_client<T: Database>(db: &mut T, args: RemoveArgs) -> Result<PostScriptArgs, String> {
+fn remove_client<T: Database>(
+    db: &mut T,
+    args: RemoveArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    let mut flags = args.target.given_flags();
+    if args.name.is_some() {
+        flags.insert(0, "--name");
+    }
+    check_conflicts(&flags)?;
+    if let Some(target) = get_target(&args.target) {
+        for client in select_targets(db, &target)? {
+            db.remove_client(&client.name)?;
+        }
+        confirm_changes(&db.pending_changes()?, "remove", &args.target.confirm)?;
+        return Ok(None);
+    }
+
     let name = args.name.unwrap_or_else(input::get_client_name);
-    input::validators::validate_name(&name)?;
+    input::validators::validate_name(&name, config.limits.max_name_length)?;
     db.remove_client(&name)?;
+    // one client isn't confirmed, but a dry run still shows what would change
+    if args.target.confirm.dry_run {
+        confirm_changes(&db.pending_changes()?, "remove", &args.target.confirm)?;
+    }
     Ok(Some(vec![name]))
 }
 
-fn list_clients<T: Database>(db: &mut T, args: ListArgs) -> Result<PostScriptArgs, String> {
-    let mut clients = db.list_clients()?;
-    clients.sort_by_key(|client| client.expire_time);
-    clients.reverse();
+fn restore_client<T: Database>(db: &mut T, args: RestoreArgs) -> Result<PostScriptArgs, String> {
+    let name = args.name.unwrap_or_else(input::get_client_name);
+    db.restore_client(&name)?;
+    Ok(Some(vec![name]))
+}
+
+fn purge_clients<T: Database>(db: &mut T, args: PurgeArgs) -> Result<PostScriptArgs, String> {
+    let archived_before = Utc::now() - Duration::days(args.older_than.into());
+    for name in db.purge_clients(archived_before)? {
+        println!("{}", style(format!("purged {name}")).yellow());
+    }
+    Ok(None)
+}
 
-    let mut report = Report::new(["name", "months left", "seller", "info"].to_vec());
+fn list_clients<T: Database>(
+    db: &mut T,
+    args: ListArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    prepare_output(&args.output);
+    let query = ClientQuery {
+        archived: args.archived,
+        expired: args.expired,
+        expiring_within: args.expiring_within,
+        seller: args.seller,
+        info_contains: args.info_contains,
+        name_glob: args.name_glob,
+        search: args.search,
+        tag: args.tag,
+        sort: match args.sort {
+            ListSort::Name => ClientSort::Name,
+            ListSort::Expire => ClientSort::Expire,
+            ListSort::LastPayment => ClientSort::LastPayment,
+            ListSort::TotalPaid => ClientSort::TotalPaid,
+        },
+        limit: args.limit,
+    };
+    let clients = db.query_clients(&query)?;
+
+    if args.output.format != Format::Table {
+        let records: Vec<client_report::ClientRecord> = clients
+            .into_iter()
+            .map(client_report::ClientRecord::new)
+            .collect();
+        if args.output.format == Format::Json {
+            println!("{}", serde_json::to_string_pretty(&records).unwrap());
+        } else {
+            client_report::records_report(records).show_as(args.output.format, false);
+        }
+        return Ok(None);
+    }
+
+    let mut columns = ["name", "months left", "seller", "info"].to_vec();
+    if args.verbose {
+        columns.extend(["contact", "referred by", "since", "notes"]);
+    }
+    let mut report = Report::new(columns);
     for client in clients {
         let name = style(client.name).cyan().to_string();
-        let days_left = client_report::calculate_days_left(args.verbose, client.expire_time);
+        let days_left = match client.archived_at {
+            Some(archived_at) => style(format!("archived {}", archived_at.format("%Y-%m-%d")))
+                .red()
+                .to_string(),
+            None => client_report::calculate_days_left(
+                args.verbose,
+                client.expire_time,
+                config.display.warn_days,
+            ),
+        };
         let sellers = client_report::calculate_sellers(&client.payments);
         let info = style(client.info.unwrap_or("".to_string()))
             .black()
             .bright()
             .to_string();
 
-        report.add_item([name, days_left, sellers, info].to_vec());
+        let mut item = [name, days_left, sellers, info].to_vec();
+        if args.verbose {
+            let profile = client.profile;
+            let contacts: Vec<String> = [profile.contact, profile.telegram, profile.email]
+                .into_iter()
+                .flatten()
+                .collect();
+            item.extend([
+                contacts.join(" "),
+                profile.referred_by.unwrap_or_default(),
+                client
+                    .created_at
+                    .map_or(String::new(), |date| date.format("%Y-%m-%d").to_string()),
+                profile.notes.unwrap_or_default(),
+            ]);
+        }
+        report.add_item(item);
     }
 
-    report.show(args.trim_whitespace);
+    report.show(args.output.trim_whitespace);
     Ok(None)
 }
 
-fn rename_client<T: Database>(db: &mut T, args: RenameArgs) -> Result<PostScriptArgs, String> {
+fn rename_client<T: Database>(
+    db: &mut T,
+    args: RenameArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
     let old_name = args.old_name.unwrap_or_else(input::get_client_name);
     let new_name = args.new_name.unwrap_or_else(input::get_client_new_name);
 
-    input::validators::validate_name(&new_name)?;
+    input::validators::validate_name(&new_name, config.limits.max_name_length)?;
 
     db.rename_client(&old_name, &new_name)?;
     Ok(Some(vec![old_name, new_name]))
 }
 
 fn set_client_info<T: Database>(db: &mut T, args: SetInfoArgs) -> Result<PostScriptArgs, String> {
-    if (args.all as i32) + (args.match_info.is_some() as i32) + (args.name.is_some() as i32) > 1 {
+    let mut flags: Vec<&str> = [
+        ("--all", args.all),
+        ("--match-info", args.match_info.is_some()),
+        ("--name", args.name.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(flag, is_given)| is_given.then_some(flag))
+    .collect();
+    flags.extend(args.target.given_flags());
+    // flags it always had keep their old error
+    let legacy_flags = ["--all", "--match-info", "--name"];
+    if flags.len() > 1 && flags.iter().all(|flag| legacy_flags.contains(flag)) {
         return Err("--match-info and --all and --name conflicts with each other".to_string());
     }
+    check_conflicts(&flags)?;
 
     let target: Target = if args.all {
         Target::All
     } else if let Some(old_info) = &args.match_info {
         Target::MatchInfo(old_info.to_string())
+    } else if let Some(target) = get_target(&args.target) {
+        target
     } else {
         Target::OnePerson(args.name.unwrap_or_else(input::get_client_name))
     };
 
     let last_info = match &target {
-        Target::All => "".to_string(),
         Target::MatchInfo(old_info) => old_info.clone(),
-        Target::OnePerson(name) => db.get_client_info(&name)?,
+        Target::OnePerson(name) => db.get_client_info(name)?,
+        _ => "".to_string(),
     };
     let new_info = args
         .info
         .unwrap_or_else(|| input::get_info(Some(&last_info)));
-    db.set_client_info(target, &new_info)?;
+    if let Target::OnePerson(_) = target {
+        db.set_client_info(target, &new_info)?;
+        // one client isn't confirmed, but a dry run still shows what would change
+        if args.target.confirm.dry_run {
+            confirm_changes(&db.pending_changes()?, "set info of", &args.target.confirm)?;
+        }
+        return Ok(None);
+    }
+
+    let clients = select_targets(db, &target)?;
+    db.set_client_info(Target::Names(get_names(&clients)), &new_info)?;
+    confirm_changes(&db.pending_changes()?, "set info of", &args.target.confirm)?;
+
+    Ok(None)
+}
+
+fn bulk_edit_clients<T: Database>(
+    db: &mut T,
+    args: BulkEditArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    check_conflicts(&args.target.given_flags())?;
+    let target = get_target(&args.target).ok_or(
+        "a target is needed, pass one of --names, --name-glob, --seller, --expiring-within, --tag or --info-contains",
+    )?;
+    if args.days.is_none()
+        && args.info.is_none()
+        && args.add_tag.is_empty()
+        && args.remove_tag.is_empty()
+    {
+        return Err("nothing to edit, pass --days, --info, --add-tag or --remove-tag".to_string());
+    }
+    if let Some(info) = &args.info {
+        input::validators::validate_info(info, config.limits.max_info_length)?;
+    }
+    for tag in &args.add_tag {
+        input::validators::validate_tag(tag, config.limits.max_name_length)?;
+    }
+
+    let names = get_names(&select_targets(db, &target)?);
+    if let Some(days) = args.days {
+        let reason = args.reason.as_deref().unwrap_or("bulk-edit");
+        db.shift_expire_times(days, Target::Names(names.clone()), reason)?;
+    }
+    if let Some(info) = &args.info {
+        db.set_client_info(Target::Names(names.clone()), info)?;
+    }
+    for name in &names {
+        if !args.add_tag.is_empty() {
+            db.add_tags(name, &args.add_tag)?;
+        }
+        if !args.remove_tag.is_empty() {
+            db.remove_tags(name, &args.remove_tag)?;
+        }
+    }
+    confirm_changes(&db.pending_changes()?, "edit", &args.target.confirm)?;
 
     Ok(None)
 }
 
-fn cleanup<T: Database>(db: &mut T) -> Result<PostScriptArgs, String> {
+fn cleanup<T: Database>(
+    db: &mut T,
+    args: CleanupArgs,
+    config: &Config,
+    hooks: &Hooks,
+    stopped_error: &mut Option<String>,
+) -> Result<PostScriptArgs, String> {
+    let grace = args.grace.unwrap_or(config.cleanup.grace_days);
     let now_time = Utc::now();
+    let clients: Vec<Client> = db
+        .list_clients()?
+        .into_iter()
+        .filter(|client| (now_time - client.expire_time).num_days() >= grace.into())
+        .filter(|client| {
+            args.tag
+                .as_ref()
+                .is_none_or(|tag| client.tags.contains(tag))
+        })
+        .collect();
+    if clients.is_empty() {
+        println!("{}", style("nothing to clean up").green());
+        return Ok(None);
+    }
 
-    let clients = db.list_clients()?;
-    for client in clients {
-        let is_expired_five_days_ago = (now_time - client.expire_time).num_days() >= 5;
-        if is_expired_five_days_ago {
-            db.remove_client(&client.name)?;
-            run_post_script("delete", vec![client.name.clone()])?;
-            println!("{}", style(format!("deleted {}", client.name)).yellow());
+    let format_expire = |client: &Client| client.expire_time.format("%Y-%m-%d").to_string();
+    let planned_changes: Vec<ClientChange> = clients
+        .iter()
+        .map(|client| ClientChange {
+            before: Some(client.clone()),
+            after: Some(Client {
+                archived_at: Some(now_time),
+                ..client.clone()
+            }),
+        })
+        .collect();
+    // hooks act outside of database, so clients are confirmed before anything runs, a dry run runs
+    // no hook and is previewed from what it changed like other bulk operations
+    if !args.confirm.dry_run {
+        confirm_changes(&planned_changes, "remove", &args.confirm)?;
+    }
+
+    hooks.run(
+        Phase::Pre,
+        "cleanup",
+        &get_changed_names(&planned_changes),
+        planned_changes.clone(),
+    )?;
+
+    let mut report = Report::new(["name", "expired", "status"].to_vec());
+    let mut failed_count = 0;
+    let mut archived_names: Vec<&str> = Vec::new();
+    for (client, change) in clients.iter().zip(planned_changes) {
+        // script runs first so a failed client is left untouched when keeping going
+        let script_result = hooks.run(
+            Phase::Post,
+            "delete",
+            std::slice::from_ref(&client.name),
+            vec![change],
+        );
+        let status = match script_result {
+            Ok(()) => {
+                db.remove_client(&client.name)?;
+                archived_names.push(&client.name);
+                style("archived".to_string()).green()
+            }
+            Err(error) if args.keep_going => {
+                failed_count += 1;
+                style(format!("failed: {error}")).red()
+            }
+            // clients before it are already deprovisioned by their scripts, so they are kept archived
+            Err(error) => {
+                let archived = if archived_names.is_empty() {
+                    "no client is archived".to_string()
+                } else {
+                    format!("already archived: {}", archived_names.join(", "))
+                };
+                *stopped_error = Some(format!(
+                    "cleanup stopped at '{}', {archived}, pass --keep-going to skip failed clients: {error}",
+                    client.name
+                ));
+                report.add_item(
+                    [
+                        style(&client.name).cyan().to_string(),
+                        format_expire(client),
+                        style("failed").red().to_string(),
+                    ]
+                    .to_vec(),
+                );
+                break;
+            }
+        };
+
+        report.add_item(
+            [
+                style(&client.name).cyan().to_string(),
+                format_expire(client),
+                status.to_string(),
+            ]
+            .to_vec(),
+        );
+    }
+
+    let changes = db.pending_changes()?;
+    if args.confirm.dry_run {
+        confirm_changes(&changes, "remove", &args.confirm)?;
+        return Ok(None);
+    }
+    hooks.run(
+        Phase::Post,
+        "cleanup",
+        &get_changed_names(&changes),
+        changes,
+    )?;
+
+    report.show(false);
+    if failed_count > 0 {
+        println!(
+            "{}",
+            style(format!(
+                "{failed_count} of {} clients are kept because their post script failed",
+                clients.len()
+            ))
+            .yellow()
+        );
+    }
+    Ok(None)
+}
+
+fn manage_tags<T: Database>(
+    db: &mut T,
+    args: TagArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    match args.command {
+        TagCommands::Add(args) => {
+            let name = args.name.unwrap_or_else(input::get_client_name);
+            for tag in &args.tags {
+                input::validators::validate_tag(tag, config.limits.max_name_length)?;
+            }
+            db.add_tags(&name, &args.tags)?;
+            Ok(Some(vec![name]))
+        }
+        TagCommands::Remove(args) => {
+            let name = args.name.unwrap_or_else(input::get_client_name);
+            db.remove_tags(&name, &args.tags)?;
+            Ok(Some(vec![name]))
+        }
+        TagCommands::List => {
+            let mut report = Report::new(["tag", "clients"].to_vec());
+            for (tag, count) in db.list_tags()? {
+                report.add_item([style(tag).cyan().to_string(), count.to_string()].to_vec());
+            }
+            report.show(false);
+            Ok(None)
         }
     }
+}
+
+fn export<T: Database>(db: &mut T, args: ExportArgs) -> Result<PostScriptArgs, String> {
+    let format = match args.format {
+        Some(format) => format,
+        None => FileFormat::from_path(&args.out)?,
+    };
+    let dump = transfer::read(db)?;
+    transfer::export_file(&dump, format, &args.out)?;
 
+    println!(
+        "{}",
+        style(format!(
+            "exported {} clients to '{}'",
+            dump.clients.len(),
+            args.out.to_str().unwrap()
+        ))
+        .green()
+    );
     Ok(None)
 }
 
-fn get_command_post_script(command: &Commands, skip: bool) -> Option<&'static str> {
-    if skip {
-        println!("{}", style("skipping post script!").yellow());
-        return None;
+fn import<T: Database>(
+    db: &mut T,
+    args: ImportArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    let flags: Vec<&str> = [("--merge", args.merge), ("--replace", args.replace)]
+        .into_iter()
+        .filter_map(|(flag, is_given)| is_given.then_some(flag))
+        .collect();
+    check_conflicts(&flags)?;
+    let format = match args.format {
+        Some(format) => format,
+        None => FileFormat::from_path(&args.from)?,
+    };
+    let dump = transfer::import_file(&args.from, format)?;
+
+    // everything is checked before the first write
+    for seller in &dump.sellers {
+        input::validators::validate_name(seller, config.limits.max_name_length)
+            .map_err(|e| format!("cannot import seller '{seller}': {e}"))?;
+    }
+    for plan in &dump.plans {
+        input::validators::validate_name(&plan.name, config.limits.max_name_length)
+            .map_err(|e| format!("cannot import plan '{}': {e}", plan.name))?;
     }
+    for client in &dump.clients {
+        validate_imported_client(client, config)
+            .map_err(|e| format!("cannot import client '{}': {e}", client.name))?;
+    }
+
+    let mut existing_clients = db.list_clients()?;
+    existing_clients.extend(db.list_archived_clients()?);
+    let conflicts: Vec<&str> = dump
+        .clients
+        .iter()
+        .filter(|client| existing_clients.iter().any(|c| c.name == client.name))
+        .map(|client| client.name.as_str())
+        .collect();
+    if !conflicts.is_empty() && !args.merge && !args.replace {
+        return Err(format!(
+            "clients already exist: {}, pass --merge or --replace",
+            conflicts.join(", ")
+        ));
+    }
+
+    transfer::add_missing(db, &dump)?;
+    for client in &dump.clients {
+        let existing = existing_clients.iter().find(|c| c.name == client.name);
+        match existing {
+            Some(existing) if args.merge => db.import_client(&transfer::merge(existing, client))?,
+            _ => db.import_client(client)?,
+        }
+    }
+
+    println!(
+        "{}",
+        style(format!(
+            "imported {} clients, {} of them already existed",
+            dump.clients.len(),
+            conflicts.len()
+        ))
+        .green()
+    );
+    Ok(None)
+}
+
+fn validate_imported_client(client: &Client, config: &Config) -> Result<(), String> {
+    input::validators::validate_name(&client.name, config.limits.max_name_length)?;
+    // info used to be optional
+    if let Some(info) = client.info.as_deref().filter(|info| !info.is_empty()) {
+        input::validators::validate_info(info, config.limits.max_info_length)?;
+    }
+    if let Some(email) = &client.profile.email {
+        input::validators::validate_email(email)?;
+    }
+    for tag in &client.tags {
+        input::validators::validate_tag(tag, config.limits.max_name_length)?;
+    }
+    if client.payments.is_empty() {
+        return Err("client has no payment".to_string());
+    }
+    // payments are history, their seller may be removed since then
+    for payment in &client.payments {
+        input::validators::validate_name(&payment.seller, config.limits.max_name_length)?;
+    }
+    Ok(())
+}
+
+fn notify<T: Database>(
+    db: &mut T,
+    args: NotifyArgs,
+    config: &Config,
+    hooks: &Hooks,
+) -> Result<PostScriptArgs, String> {
+    let mut thresholds = config.notify.thresholds.clone();
+    thresholds.sort();
+    let spool_path = Path::new(&get_data_path()?).join(&config.notify.spool_file);
+    let use_hook = hooks.has_scripts(Phase::Post, "notify");
+
+    let now_time = Utc::now();
+    let mut report = Report::new(["name", "expires", "threshold", "status"].to_vec());
+    let mut notified_count = 0;
+    let mut failed_count = 0;
+    for client in db.list_clients()? {
+        // only the tightest threshold is sent, so a client found late gets one notification
+        let days_left = client.expire_time - now_time;
+        let threshold = match thresholds
+            .iter()
+            .find(|threshold| days_left <= Duration::days((**threshold).into()))
+        {
+            Some(threshold) => *threshold,
+            None => continue,
+        };
+        if db.is_notified(&client.name, threshold, &client.expire_time)? {
+            continue;
+        }
+
+        let label = match threshold {
+            0 => "expired".to_string(),
+            days => format!("{days}d"),
+        };
+        // skipped ones aren't recorded either, so they're sent on a later run
+        let status = if args.dry_run || hooks.is_skipped(Phase::Post) {
+            style("not sent".to_string()).yellow()
+        } else {
+            let result = if use_hook {
+                hooks.run(
+                    Phase::Post,
+                    "notify",
+                    &[client.name.clone(), label.clone()],
+                    vec![ClientChange {
+                        before: Some(client.clone()),
+                        after: Some(client.clone()),
+                    }],
+                )
+            } else {
+                append_to_spool(&spool_path, &client, &label)
+            };
+            match result {
+                Ok(()) => {
+                    db.add_notification(&Notification {
+                        client_name: client.name.clone(),
+                        threshold,
+                        expire_time: client.expire_time,
+                        sent_at: now_time,
+                    })?;
+                    style("sent".to_string()).green()
+                }
+                // not recorded so it's sent again on next run
+                Err(error) => {
+                    failed_count += 1;
+                    style(format!("failed: {error}")).red()
+                }
+            }
+        };
+
+        notified_count += 1;
+        report.add_item(
+            [
+                style(&client.name).cyan().to_string(),
+                client.expire_time.format("%Y-%m-%d").to_string(),
+                label,
+                status.to_string(),
+            ]
+            .to_vec(),
+        );
+    }
+
+    if notified_count == 0 {
+        println!("{}", style("nothing to notify").green());
+        return Ok(None);
+    }
+    report.show(false);
+    if failed_count > 0 {
+        println!(
+            "{}",
+            style(format!(
+                "{failed_count} notifications failed, they are sent on next run"
+            ))
+            .yellow()
+        );
+    }
+    Ok(None)
+}
+
+fn append_to_spool(spool_path: &Path, client: &Client, label: &str) -> Result<(), String> {
+    let error_msg =
+        |e: io::Error| format!("cannot write to '{}': {e}", spool_path.to_str().unwrap());
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(spool_path)
+        .map_err(error_msg)?;
+    writeln!(
+        file,
+        "{}\t{}\t{label}\t{}\t{}",
+        Utc::now().format("%Y-%m-%d %H:%M:%S"),
+        client.name,
+        client.expire_time.format("%Y-%m-%d"),
+        client.info.as_deref().unwrap_or_default()
+    )
+    .map_err(error_msg)
+}
+
+fn show_history<T: Database>(db: &mut T, args: HistoryArgs) -> Result<PostScriptArgs, String> {
+    prepare_output(&args.output);
+    let name = args.name.unwrap_or_else(input::get_client_name);
+    // make sure client exists so a typo doesn't look like a client without payments,
+    // archived clients keep their payments so they're looked up too
+    let client = match find_client(db, &name) {
+        Ok(client) => client,
+        Err(error) => db
+            .list_archived_clients()?
+            .into_iter()
+            .find(|client| client.name == name)
+            .ok_or(error)?,
+    };
+
+    let filter = PaymentFilter {
+        client_name: Some(name),
+        ..Default::default()
+    };
+
+    // adjustments aren't paid, so they're listed with a reason but left out of totals
+    let has_adjustments = !client.adjustments.is_empty();
+    let mut columns = ["date", "seller", "money", "days"].to_vec();
+    if has_adjustments {
+        columns.push("reason");
+    }
+    let mut report = Report::new(columns);
+    let mut rows = Vec::new();
+    let mut totals = payment_report::Totals::default();
+    for record in db.list_payments(&filter)? {
+        let payment = record.payment;
+        totals.add(&payment);
+        let mut row = [
+            payment_report::format_date(&payment),
+            style(&payment.seller).cyan().to_string(),
+            payment.money.to_string(),
+            payment_report::format_days(&payment),
+        ]
+        .to_vec();
+        if has_adjustments {
+            row.push("".to_string());
+        }
+        rows.push((payment.date, row));
+    }
+    for adjustment in &client.adjustments {
+        rows.push((
+            adjustment.date,
+            [
+                adjustment.date.format("%Y-%m-%d").to_string(),
+                style(&adjustment.operator).yellow().to_string(),
+                "-".to_string(),
+                format!("{:+}d", adjustment.days),
+                adjustment.reason.clone(),
+            ]
+            .to_vec(),
+        ));
+    }
+    rows.sort_by_key(|(date, _)| *date);
+    for (_, row) in rows {
+        report.add_item(row);
+    }
+
+    let mut footer = [
+        totals.count(),
+        "".to_string(),
+        totals.money(),
+        totals.days(),
+    ]
+    .to_vec();
+    if has_adjustments {
+        let adjusted_days: i64 = client.adjustments.iter().map(|a| a.days).sum();
+        footer.push(
+            style(format!("{adjusted_days:+}d adjusted"))
+                .bold()
+                .to_string(),
+        );
+    }
+    report.add_footer(footer);
+
+    report.show_as(args.output.format, args.output.trim_whitespace);
+    Ok(None)
+}
+
+fn show_ledger<T: Database>(db: &mut T, args: LedgerArgs) -> Result<PostScriptArgs, String> {
+    prepare_output(&args.output);
+    let filter = PaymentFilter {
+        client_name: None,
+        since: args.since.map(start_of_day),
+        until: args
+            .until
+            .map(|until| start_of_day(until) + Duration::days(1)),
+    };
+
+    let mut report = Report::new(["date", "client", "seller", "money", "days"].to_vec());
+    let mut totals = payment_report::Totals::default();
+    for record in db.list_payments(&filter)? {
+        let payment = record.payment;
+        totals.add(&payment);
+        report.add_item(
+            [
+                payment_report::format_date(&payment),
+                style(record.client_name).cyan().to_string(),
+                payment.seller.clone(),
+                payment.money.to_string(),
+                payment_report::format_days(&payment),
+            ]
+            .to_vec(),
+        );
+    }
+    report.add_footer(
+        [
+            totals.count(),
+            "".to_string(),
+            "".to_string(),
+            totals.money(),
+            totals.days(),
+        ]
+        .to_vec(),
+    );
+
+    report.show_as(args.output.format, args.output.trim_whitespace);
+    Ok(None)
+}
 
+fn show_report<T: Database>(db: &mut T, args: ReportArgs) -> Result<PostScriptArgs, String> {
+    match args.command {
+        ReportCommands::Revenue(args) => show_revenue(db, args),
+        ReportCommands::Settle(args) => settle(db, args),
+    }
+}
+
+fn show_revenue<T: Database>(db: &mut T, args: RevenueArgs) -> Result<PostScriptArgs, String> {
+    prepare_output(&args.output);
+    let period = match args.by {
+        RevenuePeriod::Day => Period::Day,
+        RevenuePeriod::Week => Period::Week,
+        RevenuePeriod::Month => Period::Month,
+    };
+    let filter = PaymentFilter {
+        client_name: None,
+        since: args.since.map(start_of_day),
+        until: args
+            .until
+            .map(|until| start_of_day(until) + Duration::days(1)),
+    };
+
+    let (group, group_header) = match args.group {
+        RevenueGroupBy::Seller => (RevenueGroup::Seller, "seller"),
+        RevenueGroupBy::Plan => (RevenueGroup::Plan, "plan"),
+    };
+
+    let mut report = Report::new(["period", group_header, "total", "count", "average"].to_vec());
+    let (mut total, mut count) = (0, 0);
+    for row in db.revenue(period, group, &filter)? {
+        total += row.total;
+        count += row.count;
+        report.add_item(
+            [
+                row.period,
+                style(row.group).cyan().to_string(),
+                row.total.to_string(),
+                row.count.to_string(),
+                format!("{:.1}", row.total as f64 / row.count as f64),
+            ]
+            .to_vec(),
+        );
+    }
+    if count > 0 {
+        report.add_footer(
+            [
+                style("total").bold().to_string(),
+                "".to_string(),
+                style(total).bold().to_string(),
+                style(count).bold().to_string(),
+                style(format!("{:.1}", total as f64 / count as f64))
+                    .bold()
+                    .to_string(),
+            ]
+            .to_vec(),
+        );
+    }
+
+    report.show_as(args.output.format, args.output.trim_whitespace);
+    Ok(None)
+}
+
+fn settle<T: Database>(db: &mut T, args: SettleArgs) -> Result<PostScriptArgs, String> {
+    let sellers = args.between;
+    if sellers.len() < 2 {
+        return Err("--between needs at least two sellers".to_string());
+    }
+    if sellers
+        .iter()
+        .enumerate()
+        .any(|(index, seller)| sellers[..index].contains(seller))
+    {
+        return Err("--between contains duplicate sellers".to_string());
+    }
+
+    let split = if args.split.is_empty() {
+        vec![1; sellers.len()]
+    } else {
+        args.split
+    };
+    let split_sum: u32 = split.iter().sum();
+    if split.len() != sellers.len() || split_sum == 0 {
+        return Err(
+            "--split must have one non zero share for each seller of --between".to_string(),
+        );
+    }
+
+    let next_month = if args.month.month() == 12 {
+        NaiveDate::from_ymd(args.month.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd(args.month.year(), args.month.month() + 1, 1)
+    };
+    let filter = PaymentFilter {
+        client_name: None,
+        since: Some(start_of_day(args.month)),
+        until: Some(start_of_day(next_month)),
+    };
+    let revenue = db.revenue(Period::Month, RevenueGroup::Seller, &filter)?;
+
+    let collected: Vec<i64> = sellers
+        .iter()
+        .map(|seller| {
+            revenue
+                .iter()
+                .filter(|row| &row.group == seller)
+                .map(|row| row.total as i64)
+                .sum()
+        })
+        .collect();
+    let total: i64 = collected.iter().sum();
+
+    let mut report = Report::new(["seller", "collected", "share", "balance"].to_vec());
+    let mut balances: Vec<(String, i64)> = Vec::new();
+    for (index, seller) in sellers.iter().enumerate() {
+        let share = (total as f64 * split[index] as f64 / split_sum as f64).round() as i64;
+        let balance = collected[index] - share;
+        balances.push((seller.clone(), balance));
+        report.add_item(
+            [
+                style(seller).cyan().to_string(),
+                collected[index].to_string(),
+                share.to_string(),
+                format!("{balance:+}"),
+            ]
+            .to_vec(),
+        );
+    }
+    report.show(args.trim_whitespace);
+
+    // sellers that collected more than their share pay the ones that collected less
+    let mut debtors: Vec<(String, i64)> = balances.iter().filter(|b| b.1 > 0).cloned().collect();
+    let mut creditors: Vec<(String, i64)> = balances
+        .into_iter()
+        .filter(|b| b.1 < 0)
+        .map(|(seller, balance)| (seller, -balance))
+        .collect();
+    let mut is_settled = true;
+    for debtor in debtors.iter_mut() {
+        for creditor in creditors.iter_mut() {
+            let amount = debtor.1.min(creditor.1);
+            if amount == 0 {
+                continue;
+            }
+            debtor.1 -= amount;
+            creditor.1 -= amount;
+            is_settled = false;
+            println!(
+                "{}",
+                style(format!("{} owes {} {amount}", debtor.0, creditor.0)).yellow()
+            );
+        }
+    }
+    if is_settled {
+        println!("{}", style("nothing to settle").green());
+    }
+
+    Ok(None)
+}
+
+// styles are only meant for humans, machine readable formats shouldn't contain them
+fn prepare_output(output: &OutputArgs) {
+    if output.format != Format::Table {
+        console::set_colors_enabled(false);
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> chrono::DateTime<Utc> {
+    chrono::DateTime::from_utc(date.and_hms(0, 0, 0), Utc)
+}
+
+fn manage_sellers<T: Database>(
+    db: &mut T,
+    args: SellerArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    match args.command {
+        SellerCommands::Add(args) => {
+            let name = args.name.unwrap_or_else(input::get_seller_name);
+            input::validators::validate_name(&name, config.limits.max_name_length)?;
+            db.add_seller(&name)?;
+        }
+        SellerCommands::Remove(args) => {
+            let name = args.name.unwrap_or_else(input::get_seller_name);
+            db.remove_seller(&name)?;
+        }
+        SellerCommands::List => {
+            let mut report = Report::new(["name"].to_vec());
+            for seller in db.list_sellers()? {
+                report.add_item([style(seller).cyan().to_string()].to_vec());
+            }
+            report.show(false);
+        }
+        SellerCommands::Rename(args) => {
+            let old_name = args.old_name.unwrap_or_else(input::get_seller_name);
+            let new_name = args.new_name.unwrap_or_else(input::get_seller_new_name);
+            input::validators::validate_name(&new_name, config.limits.max_name_length)?;
+            db.rename_seller(&old_name, &new_name)?;
+        }
+    }
+
+    Ok(None)
+}
+
+fn manage_plans<T: Database>(
+    db: &mut T,
+    args: PlanArgs,
+    config: &Config,
+) -> Result<PostScriptArgs, String> {
+    match args.command {
+        PlanCommands::Add(args) => {
+            let name = args.name.unwrap_or_else(input::get_plan_name);
+            input::validators::validate_name(&name, config.limits.max_name_length)?;
+            let plan = Plan {
+                name,
+                days: args
+                    .days
+                    .unwrap_or_else(|| input::get_days(config.defaults.days)),
+                money: args
+                    .money
+                    .unwrap_or_else(|| input::get_money_amount(config.defaults.money)),
+            };
+            db.add_plan(&plan)?;
+        }
+        PlanCommands::Remove(args) => {
+            let name = args.name.unwrap_or_else(input::get_plan_name);
+            db.remove_plan(&name)?;
+        }
+        PlanCommands::List => {
+            let mut report = Report::new(["name", "days", "money"].to_vec());
+            for plan in db.list_plans()? {
+                report.add_item(
+                    [
+                        style(plan.name).cyan().to_string(),
+                        plan.days.to_string(),
+                        plan.money.to_string(),
+                    ]
+                    .to_vec(),
+                );
+            }
+            report.show(false);
+        }
+    }
+
+    Ok(None)
+}
+
+fn manage_hooks<T: Database>(
+    db: &mut T,
+    args: HooksArgs,
+    hooks: &Hooks,
+) -> Result<PostScriptArgs, String> {
+    match args.command {
+        HooksCommands::List => {
+            let mut report =
+                Report::new(["id", "date", "script", "client", "attempts", "error"].to_vec());
+            for hook in db.list_pending_hooks()? {
+                report.add_item(
+                    [
+                        hook.id.to_string(),
+                        hook.date.format("%Y-%m-%d %H:%M").to_string(),
+                        style(hook.script).cyan().to_string(),
+                        hook.client,
+                        hook.attempts.to_string(),
+                        style(hook.error).red().to_string(),
+                    ]
+                    .to_vec(),
+                );
+            }
+            report.show(false);
+        }
+        HooksCommands::Retry(args) => {
+            let mut pending_hooks = db.list_pending_hooks()?;
+            if let Some(id) = args.id {
+                pending_hooks.retain(|hook| hook.id == id);
+                if pending_hooks.is_empty() {
+                    return Err(format!("pending hook {id} doesn't exists!"));
+                }
+            }
+
+            let mut report = Report::new(["id", "script", "client", "status"].to_vec());
+            let mut failed_count = 0;
+            for mut hook in pending_hooks {
+                let status = match hooks.retry(&mut hook) {
+                    Ok(()) => {
+                        db.remove_pending_hook(hook.id)?;
+                        style("done".to_string()).green()
+                    }
+                    Err(error) => {
+                        failed_count += 1;
+                        db.update_pending_hook(&hook)?;
+                        style(format!("failed: {error}")).red()
+                    }
+                };
+                report.add_item(
+                    [
+                        hook.id.to_string(),
+                        style(&hook.script).cyan().to_string(),
+                        hook.client.clone(),
+                        status.to_string(),
+                    ]
+                    .to_vec(),
+                );
+            }
+
+            report.show(false);
+            if failed_count > 0 {
+                println!(
+                    "{}",
+                    style(format!("{failed_count} hooks are still pending")).yellow()
+                );
+            }
+        }
+        HooksCommands::Log(args) => {
+            for (name, content) in hooks.recent_logs(args.limit, args.script.as_deref())? {
+                println!("{}", style(name).cyan());
+                print!("{content}");
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn manage_db(conn: &mut Connection, args: DbArgs) -> Result<(), String> {
+    match args.command {
+        DbCommands::Migrate(args) => {
+            let migrations = if args.dry_run {
+                migrations::pending(conn)?
+            } else {
+                migrations::migrate(conn)?
+            };
+
+            if migrations.is_empty() {
+                println!(
+                    "{}",
+                    style(format!(
+                        "database schema is up to date (version {})",
+                        migrations::current_version(conn)?
+                    ))
+                    .green()
+                );
+                return Ok(());
+            }
+
+            let status = if args.dry_run { "pending" } else { "applied" };
+            let mut report = Report::new(["status", "version", "description"].to_vec());
+            for migration in migrations {
+                report.add_item(
+                    [
+                        style(status).yellow().to_string(),
+                        migration.version.to_string(),
+                        migration.description.to_string(),
+                    ]
+                    .to_vec(),
+                );
+            }
+            report.show(false);
+        }
+    }
+
+    Ok(())
+}
+
+// taken before database is opened, so it's the state right before the command
+fn auto_backup(
+    conn: &Connection,
+    backups_path: &Path,
+    config: &BackupConfig,
+    operation: &str,
+) -> Result<(), String> {
+    // a database that is just created has nothing to lose
+    if migrations::current_version(conn)? == 0 {
+        return Ok(());
+    }
+    match config.schedule {
+        BackupSchedule::Never => return Ok(()),
+        BackupSchedule::Daily => {
+            let backups = backup::list(backups_path)?;
+            if backups
+                .last()
+                .is_some_and(|backup| Utc::now() - backup.date < Duration::days(1))
+            {
+                return Ok(());
+            }
+        }
+        BackupSchedule::Command => {}
+    }
+
+    backup::create(conn, backups_path, operation)?;
+    backup::rotate(backups_path, config.keep)
+}
+
+fn manage_backups(
+    conn: &mut Connection,
+    backups_path: &Path,
+    args: BackupArgs,
+    config: &BackupConfig,
+) -> Result<(), String> {
+    match args.command {
+        BackupCommands::List => {
+            let mut report = Report::new(["id", "label", "date", "size"].to_vec());
+            for backup in backup::list(backups_path)? {
+                report.add_item(
+                    [
+                        backup.id,
+                        backup.label,
+                        backup.date.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        format!("{}K", backup.size.div_ceil(1024)),
+                    ]
+                    .to_vec(),
+                );
+            }
+            report.show(false);
+        }
+        BackupCommands::Create => {
+            let backup = backup::create(conn, backups_path, "manual")?;
+            backup::rotate(backups_path, config.keep)?;
+            println!(
+                "{}",
+                style(format!("backup '{}' is created", backup.id)).green()
+            );
+        }
+        BackupCommands::Restore(args) => {
+            let backup = backup::find(backups_path, &args.id)?;
+            backup::verify(&backup)?;
+            if !args.yes
+                && !input::confirm(&format!("replace database with backup '{}'?", backup.id))?
+            {
+                return Err("restore is canceled".to_string());
+            }
+
+            let previous = backup::create(conn, backups_path, "restore")?;
+            backup::restore(conn, &backup)?;
+            // the restored backup may be the oldest one, so it's rotated only after restoring
+            backup::rotate(backups_path, config.keep)?;
+            println!(
+                "{}",
+                style(format!(
+                    "database is restored from backup '{}', previous database is kept as backup '{}'",
+                    backup.id, previous.id
+                ))
+                .green()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn manage_config(config: &Config, config_path: &Path, args: ConfigArgs) -> Result<(), String> {
+    match args.command {
+        ConfigCommands::Show => print!("{}", config.to_toml()),
+        // reaching here means config is loaded and validated
+        ConfigCommands::Validate => {
+            if config_path.is_file() {
+                println!(
+                    "{}",
+                    style(format!(
+                        "config '{}' is valid",
+                        config_path.to_str().unwrap()
+                    ))
+                    .green()
+                );
+            } else {
+                println!(
+                    "{}",
+                    style(format!(
+                        "config '{}' doesn't exist, defaults are used",
+                        config_path.to_str().unwrap()
+                    ))
+                    .yellow()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn show_audit<T: Database>(db: &mut T, args: AuditArgs) -> Result<PostScriptArgs, String> {
+    prepare_output(&args.output);
+    let filter = AuditFilter {
+        client_name: args.name,
+        operation: args.operation,
+        limit: Some(args.limit),
+    };
+    let entries = db.list_audit_entries(&filter)?;
+
+    if args.output.format == Format::Json {
+        println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+        return Ok(None);
+    }
+
+    let mut headers = ["id", "date", "user", "operation", "clients", "arguments"].to_vec();
+    if args.verbose {
+        headers.push("changes");
+    }
+    let mut report = Report::new(headers);
+    for entry in entries {
+        let mut item = [
+            entry.id.to_string(),
+            entry.date.format("%Y-%m-%d %H:%M:%S").to_string(),
+            entry.user,
+            style(match entry.reverts {
+                Some(id) => format!("{} ({id})", entry.operation),
+                None => entry.operation,
+            })
+            .yellow()
+            .to_string(),
+            style(audit_report::describe_clients(&entry.changes))
+                .cyan()
+                .to_string(),
+            entry.arguments.join(" "),
+        ]
+        .to_vec();
+        if args.verbose {
+            item.push(audit_report::describe_changes(&entry.changes));
+        }
+        report.add_item(item);
+    }
+
+    report.show_as(args.output.format, args.output.trim_whitespace);
+    Ok(None)
+}
+
+fn undo<T: Database>(db: &mut T, args: UndoArgs, hooks: &Hooks) -> Result<PostScriptArgs, String> {
+    let entry = db.undo(args.id)?;
+
+    // undo changes every client from its state after the operation back to the one before
+    let changes: Vec<ClientChange> = entry
+        .changes
+        .iter()
+        .map(|change| ClientChange {
+            before: change.after.clone(),
+            after: change.before.clone(),
+        })
+        .collect();
+    let names = get_changed_names(&changes);
+    hooks.run(Phase::Pre, "undo", &names, changes.clone())?;
+    for (change, undo_change) in entry.changes.iter().zip(&changes) {
+        if let Some((event, event_args)) = get_undo_post_script(change) {
+            hooks.run(Phase::Post, event, &event_args, vec![undo_change.clone()])?;
+        }
+    }
+    hooks.run(Phase::Post, "undo", &names, changes)?;
+
+    println!(
+        "{}",
+        style(format!(
+            "undid operation {} ({}): {}",
+            entry.id,
+            entry.operation,
+            audit_report::describe_clients(&entry.changes)
+        ))
+        .yellow()
+    );
+    Ok(None)
+}
+
+// post script that brings outside world back to the state before the change, archived clients
+// are treated like deleted ones
+fn get_undo_post_script(change: &ClientChange) -> Option<(&'static str, Vec<String>)> {
+    let active = |client: &Option<Client>| {
+        client
+            .as_ref()
+            .filter(|client| client.archived_at.is_none())
+            .cloned()
+    };
+    match (active(&change.before), active(&change.after)) {
+        (None, Some(after)) => Some(("delete", vec![after.name.clone()])),
+        (Some(before), None) => Some(("add", vec![before.name.clone()])),
+        (Some(before), Some(after)) if before.name != after.name => {
+            Some(("rename", vec![after.name.clone(), before.name.clone()]))
+        }
+        (Some(before), Some(after)) if before.expire_time != after.expire_time => {
+            Some(("renew", vec![before.name.clone()]))
+        }
+        _ => None,
+    }
+}
+
+fn find_client<T: Database>(db: &T, name: &str) -> Result<Client, String> {
+    db.list_clients()?
+        .into_iter()
+        .find(|client| client.name == name)
+        .ok_or(format!("client with name '{name}' doesn't exists!"))
+}
+
+// fields that are not passed are asked when `ask` is set, otherwise they're kept
+fn get_profile(
+    args: ProfileArgs,
+    last_profile: &Profile,
+    ask: bool,
+    config: &Config,
+) -> Result<Profile, String> {
+    let get_field = |value: Option<String>, prompt: &str, last_value: &Option<String>| match value {
+        Some(value) => Some(value).filter(|value| !value.is_empty()),
+        None if ask => input::get_profile_field(prompt, last_value.as_deref()),
+        None => last_value.clone(),
+    };
+    let profile = Profile {
+        contact: get_field(args.contact, "contact", &last_profile.contact),
+        telegram: get_field(args.telegram, "telegram", &last_profile.telegram),
+        email: get_field(args.email, "email", &last_profile.email),
+        notes: get_field(args.notes, "notes", &last_profile.notes),
+        referred_by: get_field(args.referred_by, "referred by", &last_profile.referred_by),
+    };
+
+    // notes are free-form, other fields are short like info
+    for (field_name, value) in [
+        ("contact", &profile.contact),
+        ("telegram", &profile.telegram),
+        ("email", &profile.email),
+        ("referred by", &profile.referred_by),
+    ] {
+        if value
+            .as_ref()
+            .is_some_and(|value| value.len() > config.limits.max_info_length)
+        {
+            return Err(format!("cannot validate {field_name}: text is too long"));
+        }
+    }
+    if let Some(email) = &profile.email {
+        input::validators::validate_email(email)?;
+    }
+    Ok(profile)
+}
+
+fn find_plan<T: Database>(db: &T, name: &str) -> Result<Plan, String> {
+    db.list_plans()?
+        .into_iter()
+        .find(|plan| plan.name == name)
+        .ok_or(format!(
+            "plan '{name}' doesn't exist, add it with 'plan add'"
+        ))
+}
+
+// plan fills days and money that are not given, when none of them is given plans are offered
+fn get_payment_terms<T: Database>(
+    db: &T,
+    plan: Option<String>,
+    days: Option<u32>,
+    money: Option<u32>,
+    config: &Config,
+) -> Result<(Option<String>, u32, u32), String> {
+    let plan = match plan {
+        Some(plan_name) => Some(find_plan(db, &plan_name)?),
+        None if days.is_none() && money.is_none() => {
+            let plans = db.list_plans()?;
+            if plans.is_empty() {
+                None
+            } else {
+                input::get_plan(&plans)
+            }
+        }
+        None => None,
+    };
+
+    let days = days
+        .or(plan.as_ref().map(|plan| plan.days))
+        .unwrap_or_else(|| input::get_days(config.defaults.days));
+    let money = money
+        .or(plan.as_ref().map(|plan| plan.money))
+        .unwrap_or_else(|| input::get_money_amount(config.defaults.money));
+    Ok((plan.map(|plan| plan.name), days, money))
+}
+
+fn get_sellers<T: Database>(db: &T) -> Result<Vec<String>, String> {
+    let sellers = db.list_sellers()?;
+    if sellers.is_empty() {
+        return Err("there is no seller, add one with 'seller add'".to_string());
+    }
+    Ok(sellers)
+}
+
+fn get_command_operation(command: &Commands) -> Option<&'static str> {
     match &command {
         Commands::Add(_) => Some("add"),
         Commands::Renew(_) => Some("renew"),
-        Commands::Remove(_) => Some("delete"),
+        Commands::RenewAll(_) => Some("renew-all"),
+        Commands::Edit(_) => Some("edit"),
+        Commands::BulkEdit(_) => Some("bulk-edit"),
+        Commands::Remove(_) => Some("remove"),
+        Commands::Restore(_) => Some("restore"),
+        Commands::Purge(_) => Some("purge"),
         Commands::Rename(_) => Some("rename"),
+        Commands::SetInfo(_) => Some("set-info"),
+        Commands::Cleanup(_) => Some("cleanup"),
+        Commands::Undo(_) => Some("undo"),
+        Commands::Seller(args) => match args.command {
+            SellerCommands::Add(_) => Some("seller-add"),
+            SellerCommands::Remove(_) => Some("seller-remove"),
+            SellerCommands::Rename(_) => Some("seller-rename"),
+            SellerCommands::List => None,
+        },
+        Commands::Plan(args) => match args.command {
+            PlanCommands::Add(_) => Some("plan-add"),
+            PlanCommands::Remove(_) => Some("plan-remove"),
+            PlanCommands::List => None,
+        },
+        Commands::Import(_) => Some("import"),
+        Commands::Tag(args) => match args.command {
+            TagCommands::Add(_) => Some("tag-add"),
+            TagCommands::Remove(_) => Some("tag-remove"),
+            TagCommands::List => None,
+        },
         _ => None,
     }
 }
 
-fn run_post_script(script_name: &str, args: Vec<String>) -> Result<(), String> {
-    let script_path = Path::new(&get_data_path()?)
-        .join(POST_SCRIPTS_FOLDER_NAME)
-        .join(script_name);
-    let output = process::Command::new(&script_path)
-        .args(args)
-        .output()
-        .map_err(|error| {
-            format!(
-                "couldn't run post script '{}': {}",
-                script_path.to_str().unwrap(),
-                error.to_string()
-            )
-        })?;
-
-    if !output.status.success() {
-        let mut output_stderr = std::str::from_utf8(output.stderr.as_slice())
-            .unwrap()
-            .to_string();
-        if let Some(last_char) = output_stderr.chars().last() {
-            if last_char == '\n' {
-                output_stderr.pop();
-            }
-        }
+// scripts of removing and restoring kept their names from before hooks had payloads
+fn get_hook_event(operation: &str) -> &str {
+    match operation {
+        "remove" => "delete",
+        "restore" => "add",
+        _ => operation,
+    }
+}
 
-        return Err(format!(
-            "post script exited due to a failure: {}",
-            output_stderr
-        ));
+fn check_conflicts(flags: &[&str]) -> Result<(), String> {
+    if flags.len() > 1 {
+        return Err(format!("{} conflicts with each other", flags.join(" and ")));
+    }
+    Ok(())
+}
+
+fn get_target(args: &TargetArgs) -> Option<Target> {
+    if !args.names.is_empty() {
+        Some(Target::Names(args.names.clone()))
+    } else if let Some(pattern) = &args.name_glob {
+        Some(Target::NameGlob(pattern.clone()))
+    } else if let Some(seller) = &args.seller {
+        Some(Target::Seller(seller.clone()))
+    } else if let Some(days) = args.expiring_within {
+        Some(Target::ExpiringWithin(days))
+    } else if let Some(tag) = &args.tag {
+        Some(Target::Tag(tag.clone()))
+    } else {
+        args.info_contains
+            .as_ref()
+            .map(|text| Target::InfoContains(text.clone()))
+    }
+}
+
+fn select_targets<T: Database>(db: &T, target: &Target) -> Result<Vec<Client>, String> {
+    Ok(db
+        .list_clients()?
+        .into_iter()
+        .filter(|client| target.matches(client))
+        .collect())
+}
+
+// bulk operations are applied before asking, so the preview is exactly what is going to be saved
+fn confirm_changes(
+    changes: &[ClientChange],
+    action: &str,
+    confirm: &ConfirmArgs,
+) -> Result<(), String> {
+    if changes.is_empty() {
+        return Err("no client matches the target".to_string());
+    }
+    if confirm.yes && !confirm.dry_run {
+        return Ok(());
+    }
+
+    let mut report = Report::new(["name", "expires", "info", "tags"].to_vec());
+    for change in changes {
+        let describe = |client: &Option<Client>| match client {
+            Some(client) => [
+                match client.archived_at {
+                    Some(_) => "archived".to_string(),
+                    None => client.expire_time.format("%Y-%m-%d").to_string(),
+                },
+                client.info.clone().unwrap_or_default(),
+                client.tags.join(","),
+            ],
+            None => ["purged".to_string(), String::new(), String::new()],
+        };
+        let name = change
+            .before
+            .as_ref()
+            .or(change.after.as_ref())
+            .map_or(String::new(), |client| client.name.clone());
+        let mut item = [style(name).cyan().to_string()].to_vec();
+        for (old, new) in describe(&change.before)
+            .into_iter()
+            .zip(describe(&change.after))
+        {
+            item.push(if old == new {
+                old
+            } else {
+                format!("{old} -> {}", style(new).yellow())
+            });
+        }
+        report.add_item(item);
     }
+    report.show(false);
 
-    let result = std::str::from_utf8(output.stdout.as_slice())
-        .unwrap()
-        .to_string();
-    if !result.is_empty() {
-        println!("{}", result);
+    if confirm.dry_run {
+        return Ok(());
+    }
+    if !input::confirm(&format!("{action} {} clients?", changes.len()))? {
+        return Err(format!("{action} is canceled"));
     }
     Ok(())
 }
 
+fn get_names(clients: &[Client]) -> Vec<String> {
+    clients.iter().map(|client| client.name.clone()).collect()
+}
+
+fn get_changed_names(changes: &[ClientChange]) -> Vec<String> {
+    changes
+        .iter()
+        .filter_map(|change| change.after.as_ref().or(change.before.as_ref()))
+        .map(|client| client.name.clone())
+        .collect()
+}
+
 fn get_data_path() -> Result<String, String> {
     let env_name = DATA_PATH_ENV_NAME;
     env::var(env_name).map_err(|_error| {
@@ -1,7 +1,21 @@
+use clap::ValueEnum;
+use dialoguer::console::{measure_text_width, pad_str, Alignment};
+use serde_json::{Map, Value};
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Table,
+    Json,
+    Csv,
+    Tsv,
+}
+
 pub struct Report {
     headers: Vec<&'static str>,
     headers_max_len: Vec<usize>,
     items: Vec<Vec<String>>,
+    footers: Vec<Vec<String>>,
 }
 
 impl Report {
@@ -11,30 +25,53 @@ impl Report {
             headers,
             headers_max_len: vec![0; headers_len],
             items: Vec::new(),
+            footers: Vec::new(),
         }
     }
 
     pub fn add_item(&mut self, item: Vec<String>) {
+        self.update_max_len(&item);
+        self.items.push(item);
+    }
+
+    // footers are summaries like totals, they are only shown in table format
+    pub fn add_footer(&mut self, item: Vec<String>) {
+        self.update_max_len(&item);
+        self.footers.push(item);
+    }
+
+    fn update_max_len(&mut self, item: &[String]) {
         assert_eq!(item.len(), self.headers.len());
 
         for (index, headers_max_len) in self.headers_max_len.iter_mut().enumerate() {
-            let item_len = item.get(index).unwrap().len();
+            let item_len = measure_text_width(item.get(index).unwrap());
             if *headers_max_len < item_len {
                 *headers_max_len = item_len;
             }
         }
-
-        self.items.push(item);
     }
 
     pub fn show(self, trim_whitespace: bool) {
-        for item in self.items {
+        self.show_as(Format::Table, trim_whitespace);
+    }
+
+    pub fn show_as(self, format: Format, trim_whitespace: bool) {
+        match format {
+            Format::Table => self.show_table(trim_whitespace),
+            Format::Json => self.show_json(),
+            Format::Csv => self.show_delimited(','),
+            Format::Tsv => self.show_delimited('\t'),
+        }
+    }
+
+    fn show_table(self, trim_whitespace: bool) {
+        for item in self.items.iter().chain(self.footers.iter()) {
             let mut buffer: Vec<String> = Vec::new();
             for (index, column) in item.iter().enumerate() {
                 let column_text = if trim_whitespace {
-                    format!("{column}")
+                    column.to_string()
                 } else {
-                    format!("{:1$}", column, self.headers_max_len[index])
+                    pad_str(column, self.headers_max_len[index], Alignment::Left, None).to_string()
                 };
                 buffer.push(column_text);
             }
@@ -42,6 +79,47 @@ impl Report {
             println!("{}", buffer.join(" "));
         }
     }
+
+    fn show_json(self) {
+        let items: Vec<Value> = self
+            .items
+            .into_iter()
+            .map(|item| {
+                let object: Map<String, Value> = self
+                    .headers
+                    .iter()
+                    .zip(item)
+                    .map(|(header, column)| (header.to_string(), Value::String(column)))
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&items).unwrap());
+    }
+
+    fn show_delimited(self, delimiter: char) {
+        let escape = |column: &str| escape_delimited(column, delimiter);
+        let headers: Vec<String> = self.headers.iter().map(|header| escape(header)).collect();
+        println!("{}", headers.join(&delimiter.to_string()));
+
+        for item in self.items {
+            let item: Vec<String> = item.iter().map(|column| escape(column)).collect();
+            println!("{}", item.join(&delimiter.to_string()));
+        }
+    }
+}
+
+pub fn escape_delimited(column: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return column.replace(['\t', '\n'], " ");
+    }
+
+    if column.contains([delimiter, '"', '\n']) {
+        return format!("\"{}\"", column.replace('"', "\"\""));
+    }
+    column.to_string()
 }
 
+pub mod audit_report;
 pub mod client_report;
+pub mod payment_report;
@@ -0,0 +1,62 @@
+use crate::db::{audit::ClientChange, Client};
+
+pub fn describe_clients(changes: &[ClientChange]) -> String {
+    let names: Vec<String> = changes
+        .iter()
+        .map(|change| match (&change.before, &change.after) {
+            (None, Some(after)) => format!("+{}", after.name),
+            (Some(before), None) => format!("-{}", before.name),
+            (Some(before), Some(after)) if before.name != after.name => {
+                format!("{}->{}", before.name, after.name)
+            }
+            (Some(before), Some(_)) => before.name.clone(),
+            (None, None) => "?".to_string(),
+        })
+        .collect();
+    names.join(",")
+}
+
+pub fn describe_changes(changes: &[ClientChange]) -> String {
+    let descriptions: Vec<String> = changes
+        .iter()
+        .filter_map(|change| match (&change.before, &change.after) {
+            (Some(before), Some(after)) => {
+                let diff = describe_diff(before, after);
+                (!diff.is_empty()).then(|| format!("{}: {diff}", after.name))
+            }
+            _ => None,
+        })
+        .collect();
+    descriptions.join("; ")
+}
+
+fn describe_diff(before: &Client, after: &Client) -> String {
+    let mut diff: Vec<String> = Vec::new();
+    if before.expire_time != after.expire_time {
+        let format = |client: &Client| client.expire_time.format("%Y-%m-%d").to_string();
+        diff.push(format!("expire {} -> {}", format(before), format(after)));
+    }
+    if before.info != after.info {
+        let format = |client: &Client| client.info.clone().unwrap_or_default();
+        diff.push(format!("info '{}' -> '{}'", format(before), format(after)));
+    }
+    if before.payments.len() != after.payments.len() {
+        diff.push(format!(
+            "payments {} -> {}",
+            before.payments.len(),
+            after.payments.len()
+        ));
+    }
+
+    let format_payment = |client: &Client| {
+        client.payments.last().map_or("-".to_string(), |payment| {
+            format!("{}({})", payment.seller, payment.money)
+        })
+    };
+    let (last_before, last_after) = (format_payment(before), format_payment(after));
+    if last_before != last_after {
+        diff.push(format!("last payment {last_before} -> {last_after}"));
+    }
+
+    diff.join(", ")
+}
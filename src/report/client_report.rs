@@ -1,8 +1,74 @@
-use crate::db::Payment;
+use crate::db::{Client, Payment};
+use crate::report::Report;
 use chrono::{DateTime, Utc};
 use dialoguer::console::style;
+use serde::Serialize;
 
-pub fn calculate_days_left(verbose: bool, expire_time: DateTime<Utc>) -> String {
+#[derive(Serialize)]
+pub struct ClientRecord {
+    #[serde(flatten)]
+    pub client: Client,
+
+    pub days_left: i64,
+}
+
+impl ClientRecord {
+    pub fn new(client: Client) -> ClientRecord {
+        let days_left = (client.expire_time - Utc::now()).num_days();
+        ClientRecord { client, days_left }
+    }
+}
+
+// flat version of records for csv like formats, payments are packed in one column
+pub fn records_report(records: Vec<ClientRecord>) -> Report {
+    let mut report = Report::new(
+        [
+            "name",
+            "expire_time",
+            "days_left",
+            "seller",
+            "money",
+            "info",
+            "payments",
+        ]
+        .to_vec(),
+    );
+    for record in records {
+        let client = record.client;
+        let last_payment = client.payments.last().unwrap();
+        let payments: Vec<String> = client
+            .payments
+            .iter()
+            .map(|payment| {
+                let days = payment
+                    .days
+                    .map_or("-".to_string(), |days| days.to_string());
+                format!(
+                    "{} {} {} {days}",
+                    payment.date.format("%Y-%m-%d %H:%M:%S"),
+                    payment.seller,
+                    payment.money
+                )
+            })
+            .collect();
+
+        report.add_item(
+            [
+                client.name.clone(),
+                client.expire_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                record.days_left.to_string(),
+                last_payment.seller.clone(),
+                last_payment.money.to_string(),
+                client.info.clone().unwrap_or_default(),
+                payments.join("; "),
+            ]
+            .to_vec(),
+        );
+    }
+    report
+}
+
+pub fn calculate_days_left(verbose: bool, expire_time: DateTime<Utc>, warn_days: u32) -> String {
     let now_date = Utc::now();
     if expire_time < now_date {
         let expired_string = if verbose {
@@ -17,15 +83,15 @@ pub fn calculate_days_left(verbose: bool, expire_time: DateTime<Utc>) -> String
 
     let delta = expire_time - now_date;
     let num_days = delta.num_days();
-    if num_days < 15 {
+    if num_days < warn_days.into() {
         return style(format!("{num_days}d")).yellow().to_string();
     }
 
     style(format!("{num_days}d")).green().to_string()
 }
 
-pub fn calculate_sellers(payments: &Vec<Payment>) -> String {
+pub fn calculate_sellers(payments: &[Payment]) -> String {
     assert!(!payments.is_empty());
-    let last_payment = payments.iter().rev().next().unwrap();
+    let last_payment = payments.last().unwrap();
     format!("{}({})", last_payment.seller, last_payment.money)
 }
@@ -0,0 +1,40 @@
+use crate::db::Payment;
+use dialoguer::console::style;
+
+pub fn format_date(payment: &Payment) -> String {
+    payment.date.format("%Y-%m-%d").to_string()
+}
+
+pub fn format_days(payment: &Payment) -> String {
+    match payment.days {
+        Some(days) => format!("{days}d"),
+        None => "-".to_string(),
+    }
+}
+
+#[derive(Default)]
+pub struct Totals {
+    count: usize,
+    money: u32,
+    days: u32,
+}
+
+impl Totals {
+    pub fn add(&mut self, payment: &Payment) {
+        self.count += 1;
+        self.money += payment.money;
+        self.days += payment.days.unwrap_or(0);
+    }
+
+    pub fn count(&self) -> String {
+        style(format!("{} payments", self.count)).bold().to_string()
+    }
+
+    pub fn money(&self) -> String {
+        style(self.money.to_string()).bold().to_string()
+    }
+
+    pub fn days(&self) -> String {
+        style(format!("{}d", self.days)).bold().to_string()
+    }
+}
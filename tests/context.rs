@@ -5,8 +5,16 @@ use std::{
     path::{Path, PathBuf},
 };
 
+#[derive(Clone, Copy)]
+pub enum Backend {
+    Sqlite,
+    Json,
+}
+
 pub struct TestContext {
     data_path: PathBuf,
+    // passed as --backend, otherwise it's left to config
+    backend: Option<Backend>,
 }
 
 impl TestContext {
@@ -15,29 +23,57 @@ impl TestContext {
         let data_path_str = format!("/tmp/manjaliof-test-{random_indicator}");
         let data_path = Path::new(&data_path_str);
 
-        fs::create_dir(&data_path).unwrap();
+        fs::create_dir(data_path).unwrap();
         fs::create_dir(data_path.join("post_scripts")).unwrap();
 
         TestContext {
             data_path: data_path.to_path_buf(),
+            backend: None,
         }
     }
 
+    pub fn with_backend(backend: Backend) -> TestContext {
+        let mut context = TestContext::new();
+        context.backend = Some(backend);
+        context
+    }
+
     pub fn create_post_script(&self, post_script_name: &str, content: &str) {
         let script_path = &self.data_path.join("post_scripts").join(post_script_name);
+        fs::create_dir_all(script_path.parent().unwrap()).unwrap();
         fs::write(script_path, content).unwrap();
         Command::new("chmod")
-            .args(&["+x", script_path.to_str().unwrap()])
+            .args(["+x", script_path.to_str().unwrap()])
             .unwrap();
     }
 
+    pub fn data_path(&self) -> &Path {
+        &self.data_path
+    }
+
+    pub fn create_database(&self, sql: &str) {
+        let conn = rusqlite::Connection::open(self.data_path.join("data.db")).unwrap();
+        conn.execute_batch(sql).unwrap();
+    }
+
     pub fn run_command(&self) -> Command {
         let mut cmd = Command::cargo_bin("manjaliof").unwrap();
         cmd.env("MANJALIOF_DATA", &self.data_path);
+        match self.backend {
+            Some(Backend::Sqlite) => cmd.args(["--backend", "sqlite"]),
+            Some(Backend::Json) => cmd.args(["--backend", "json"]),
+            None => &mut cmd,
+        };
         cmd
     }
 }
 
+impl Default for TestContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Drop for TestContext {
     fn drop(&mut self) {
         fs::remove_dir_all(&self.data_path).unwrap();
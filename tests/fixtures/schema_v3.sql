@@ -0,0 +1,17 @@
+CREATE TABLE clients (
+    name TEXT PRIMARY KEY,
+    expire_date TEXT NOT NULL,
+    info TEXT
+);
+CREATE TABLE payments (
+    client_name TEXT NOT NULL,
+    seller TEXT NOT NULL,
+    date TEXT NOT NULL,
+    money UNSIGNED INTEGER NOT NULL,
+    days UNSIGNED INTEGER
+);
+CREATE TABLE sellers (name TEXT PRIMARY KEY);
+INSERT INTO sellers VALUES ('arian'), ('pouya');
+INSERT INTO clients VALUES ('legacy', '2020-02-01 10:00:00', 'old');
+INSERT INTO payments VALUES ('legacy', 'pouya', '2020-01-01 10:00:00', 60, NULL);
+PRAGMA user_version = 3;
@@ -0,0 +1,29 @@
+CREATE TABLE clients (
+    name TEXT PRIMARY KEY,
+    expire_date TEXT NOT NULL,
+    info TEXT
+);
+CREATE TABLE payments (
+    client_name TEXT NOT NULL,
+    seller TEXT NOT NULL,
+    date TEXT NOT NULL,
+    money UNSIGNED INTEGER NOT NULL,
+    days UNSIGNED INTEGER
+);
+CREATE TABLE sellers (name TEXT PRIMARY KEY);
+INSERT INTO sellers VALUES ('arian'), ('pouya');
+INSERT INTO clients VALUES ('legacy', '2020-02-01 10:00:00', 'old');
+INSERT INTO payments VALUES ('legacy', 'pouya', '2020-01-01 10:00:00', 60, NULL);
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,
+    user TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    arguments TEXT NOT NULL,
+    changes TEXT NOT NULL
+);
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+    BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+    BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+PRAGMA user_version = 4;
@@ -0,0 +1,56 @@
+CREATE TABLE clients (
+    name TEXT PRIMARY KEY,
+    expire_date TEXT NOT NULL,
+    info TEXT,
+    archived_at TEXT
+);
+CREATE TABLE payments (
+    client_name TEXT NOT NULL,
+    seller TEXT NOT NULL,
+    date TEXT NOT NULL,
+    money UNSIGNED INTEGER NOT NULL,
+    days UNSIGNED INTEGER,
+    plan TEXT
+);
+CREATE TABLE sellers (name TEXT PRIMARY KEY);
+INSERT INTO sellers VALUES ('arian'), ('pouya');
+INSERT INTO clients VALUES ('legacy', '2020-02-01 10:00:00', 'old', NULL);
+INSERT INTO payments VALUES ('legacy', 'pouya', '2020-01-01 10:00:00', 60, NULL, NULL);
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,
+    user TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    arguments TEXT NOT NULL,
+    changes TEXT NOT NULL,
+    reverts INTEGER REFERENCES audit_log(id)
+);
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+    BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+    BEGIN SELECT RAISE(ABORT, 'audit log is append-only'); END;
+CREATE TABLE plans (
+    name TEXT PRIMARY KEY,
+    days UNSIGNED INTEGER NOT NULL,
+    money UNSIGNED INTEGER NOT NULL
+);
+CREATE TABLE pending_hooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    event TEXT NOT NULL,
+    script TEXT NOT NULL,
+    arguments TEXT NOT NULL,
+    client TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts UNSIGNED INTEGER NOT NULL,
+    error TEXT NOT NULL
+);
+CREATE TABLE notifications (
+    client_name TEXT NOT NULL,
+    threshold UNSIGNED INTEGER NOT NULL,
+    expire_date TEXT NOT NULL,
+    sent_at TEXT NOT NULL,
+    PRIMARY KEY (client_name, threshold, expire_date)
+);
+PRAGMA user_version = 9;
@@ -1,7 +1,9 @@
 mod context;
 
-use context::TestContext;
+use chrono::Utc;
+use context::{Backend, TestContext};
 use indoc::indoc;
+use std::fs;
 
 macro_rules! args {
     ($expr:expr) => {
@@ -9,9 +11,75 @@ macro_rules! args {
     }
 }
 
-#[test]
-fn add() {
-    let context = TestContext::new();
+// tests that don't touch sqlite directly run against every backend
+macro_rules! backend_tests {
+    ($($name:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[test]
+                fn $name() {
+                    super::$name(super::Backend::Sqlite);
+                }
+            )*
+        }
+
+        mod json {
+            $(
+                #[test]
+                fn $name() {
+                    super::$name(super::Backend::Json);
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    add,
+    should_reset_db_when_add_post_script_failed,
+    list_with_trimmed_whitespace,
+    renew,
+    renew_all,
+    edit,
+    remove,
+    rename,
+    set_info,
+    set_info_should_fail_when_arguments_conflicts,
+    set_info_match_info,
+    seller_add_and_list,
+    removed_seller_keeps_payments,
+    seller_rename,
+    history,
+    plans_fill_payment_terms,
+    audit_records_mutating_commands,
+    audit_is_rolled_back_with_failed_command,
+    undo_reverts_operations_in_reverse_order,
+    config_controls_defaults_and_limits,
+    hooks_get_payload_and_pre_hooks_veto,
+    failed_hooks_are_queued_and_retried,
+    hook_output_is_streamed_and_logged,
+    client_profile_is_stored_and_searchable,
+    tags_target_bulk_operations,
+    bulk_edit_compensates_targeted_clients,
+    adjustments_are_shown_in_history_but_not_paid,
+    export_and_import_between_formats,
+    bulk_operations_preview_and_dry_run,
+    glob_classes_and_search_case_folding,
+    ledger_with_date_filters,
+    removed_clients_are_archived_until_purged,
+    report_revenue,
+    report_settle,
+    list_machine_readable_formats,
+    history_tsv_skips_totals,
+    list_filters_and_sort,
+    undo_refuses_when_client_changed_later,
+    cleanup_dry_run_and_confirmation,
+    cleanup_keep_going_with_configured_grace,
+    notify_sends_once_per_threshold,
+);
+
+fn add(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -26,9 +94,8 @@ fn add() {
         .stdout("testcase 29d pouya(60) idk\n");
 }
 
-#[test]
-fn should_reset_db_when_add_post_script_failed() {
-    let context = TestContext::new();
+fn should_reset_db_when_add_post_script_failed(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash\nexit 1");
     context
         .run_command()
@@ -43,9 +110,8 @@ fn should_reset_db_when_add_post_script_failed() {
         .stdout("");
 }
 
-#[test]
-fn list_with_trimmed_whitespace() {
-    let context = TestContext::new();
+fn list_with_trimmed_whitespace(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -69,9 +135,8 @@ fn list_with_trimmed_whitespace() {
     "});
 }
 
-#[test]
-fn renew() {
-    let context = TestContext::new();
+fn renew(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -94,9 +159,8 @@ fn renew() {
         .stdout("testcaserenew 39d arian(30) smth\n");
 }
 
-#[test]
-fn renew_all() {
-    let context = TestContext::new();
+fn renew_all(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -111,7 +175,7 @@ fn renew_all() {
 
     context
         .run_command()
-        .args(args!("renew-all --days 10"))
+        .args(args!("renew-all --days 10 --yes"))
         .assert()
         .success();
     context
@@ -125,9 +189,8 @@ fn renew_all() {
     "});
 }
 
-#[test]
-fn edit() {
-    let context = TestContext::new();
+fn edit(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -155,9 +218,8 @@ fn edit() {
         .stdout("testcase 19d arian(80) edited\n");
 }
 
-#[test]
-fn remove() {
-    let context = TestContext::new();
+fn remove(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -180,9 +242,8 @@ fn remove() {
         .stdout("");
 }
 
-#[test]
-fn rename() {
-    let context = TestContext::new();
+fn rename(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -205,9 +266,8 @@ fn rename() {
         .stdout("testcasenew 29d pouya(60) idk\n");
 }
 
-#[test]
-fn set_info() {
-    let context = TestContext::new();
+fn set_info(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -228,9 +288,8 @@ fn set_info() {
         .stdout("testcase 29d pouya(60) newinfo\n");
 }
 
-#[test]
-fn set_info_should_fail_when_arguments_conflicts() {
-    let context = TestContext::new();
+fn set_info_should_fail_when_arguments_conflicts(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     let error = "Error: --match-info and --all and --name conflicts with each other\n";
     context
         .run_command()
@@ -244,11 +303,22 @@ fn set_info_should_fail_when_arguments_conflicts() {
         .assert()
         .failure()
         .stderr(error);
+    context
+        .run_command()
+        .args(args!("set-info --tag vip --seller pouya --info idk"))
+        .assert()
+        .failure()
+        .stderr("Error: --seller and --tag conflicts with each other\n");
+    context
+        .run_command()
+        .args(args!("set-info --name idk --tag vip"))
+        .assert()
+        .failure()
+        .stderr("Error: --name and --tag conflicts with each other\n");
 }
 
-#[test]
-fn set_info_match_info() {
-    let context = TestContext::new();
+fn set_info_match_info(backend: Backend) {
+    let context = TestContext::with_backend(backend);
     context.create_post_script("add", "#!/bin/bash");
     context
         .run_command()
@@ -268,7 +338,7 @@ fn set_info_match_info() {
 
     context
         .run_command()
-        .args(args!("set-info --match-info idk --info newidk"))
+        .args(args!("set-info --match-info idk --info newidk --yes"))
         .assert()
         .success();
     context
@@ -282,3 +352,2320 @@ fn set_info_match_info() {
         testcase2 25d arian(55) nemidonam
     "});
 }
+
+fn seller_add_and_list(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context
+        .run_command()
+        .args(args!("seller list"))
+        .assert()
+        .success()
+        .stdout("arian\npouya\n");
+
+    context
+        .run_command()
+        .args(args!("seller add --name sara"))
+        .assert()
+        .success();
+    context.create_post_script("add", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("add --name testcase --days 30 --seller sara --money 60 --info idk"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 29d sara(60) idk\n");
+}
+
+fn removed_seller_keeps_payments(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("add --name testcase --days 30 --seller pouya --money 60 --info idk"))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!("seller remove --name pouya"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 29d pouya(60) idk\n");
+    context
+        .run_command()
+        .args(args!("add --name testcase2 --days 30 --seller pouya --money 60 --info idk"))
+        .assert()
+        .failure()
+        .stderr("Error: cannot validate seller: only this sellers are valid: arian\n");
+}
+
+fn seller_rename(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("add --name testcase --days 30 --seller pouya --money 60 --info idk"))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!("seller rename --old-name pouya --new-name pooya"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 29d pooya(60) idk\n");
+    context
+        .run_command()
+        .args(args!("seller rename --old-name arian --new-name pooya"))
+        .assert()
+        .failure()
+        .stderr("Error: seller 'pooya' already exists!\n");
+
+    context.run_command().arg("undo").assert().failure().stderr(
+        "Error: operation 2 (seller-rename) changed sellers or plans, it cannot be undone\n",
+    );
+    context
+        .run_command()
+        .args(args!("seller list"))
+        .assert()
+        .success()
+        .stdout("arian\npooya\n");
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 29d pooya(60) idk\n");
+}
+
+#[test]
+fn migrate_past_schemas() {
+    let fixtures = [
+        (
+            include_str!("fixtures/schema_v0.sql"),
+            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12",
+        ),
+        (
+            include_str!("fixtures/schema_v1.sql"),
+            "2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12",
+        ),
+        (
+            include_str!("fixtures/schema_v2.sql"),
+            "3, 4, 5, 6, 7, 8, 9, 10, 11, 12",
+        ),
+        (
+            include_str!("fixtures/schema_v3.sql"),
+            "4, 5, 6, 7, 8, 9, 10, 11, 12",
+        ),
+        (
+            include_str!("fixtures/schema_v4.sql"),
+            "5, 6, 7, 8, 9, 10, 11, 12",
+        ),
+        (
+            include_str!("fixtures/schema_v5.sql"),
+            "6, 7, 8, 9, 10, 11, 12",
+        ),
+        (
+            include_str!("fixtures/schema_v6.sql"),
+            "7, 8, 9, 10, 11, 12",
+        ),
+        (include_str!("fixtures/schema_v7.sql"), "8, 9, 10, 11, 12"),
+        (include_str!("fixtures/schema_v8.sql"), "9, 10, 11, 12"),
+        (include_str!("fixtures/schema_v9.sql"), "10, 11, 12"),
+        (include_str!("fixtures/schema_v10.sql"), "11, 12"),
+        (include_str!("fixtures/schema_v11.sql"), "12"),
+    ];
+    for (fixture, pending_versions) in fixtures {
+        let context = TestContext::new();
+        context.create_database(fixture);
+
+        let output = context
+            .run_command()
+            .args(args!("db migrate --dry-run"))
+            .assert()
+            .success();
+        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        let versions: Vec<&str> = stdout
+            .lines()
+            .map(|line| line.split_whitespace().nth(1).unwrap())
+            .collect();
+        assert_eq!(versions.join(", "), pending_versions);
+
+        context
+            .run_command()
+            .arg("list")
+            .assert()
+            .success()
+            .stdout("legacy expired pouya(60) old\n");
+        context
+            .run_command()
+            .args(args!("seller list"))
+            .assert()
+            .success()
+            .stdout("arian\npouya\n");
+        context
+            .run_command()
+            .args(args!("db migrate --dry-run"))
+            .assert()
+            .success()
+            .stdout("database schema is up to date (version 12)\n");
+        context
+            .run_command()
+            .args(args!("history --name legacy --trim-whitespace"))
+            .assert()
+            .success()
+            .stdout("2020-01-01 pouya 60 -\n1 payments  60 0d\n");
+    }
+}
+
+#[test]
+fn should_refuse_newer_schema() {
+    let context = TestContext::new();
+    context.create_database("PRAGMA user_version = 999;");
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr("Error: database schema version is 999 but this build only knows up to 12, please upgrade manjaliof\n");
+}
+
+fn history(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("add --name testcase --days 30 --seller pouya --money 60 --info idk"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("renew --name testcase --days 10 --seller arian --money 30 --info idk"))
+        .assert()
+        .success();
+
+    let today = Utc::now().format("%Y-%m-%d");
+    context
+        .run_command()
+        .args(args!("history --name testcase"))
+        .assert()
+        .success()
+        .stdout(format!(
+            "{today} pouya 60 30d\n{today} arian 30 10d\n2 payments       90 40d\n"
+        ));
+    context
+        .run_command()
+        .args(args!("history --name nobody"))
+        .assert()
+        .failure()
+        .stderr("Error: client with name 'nobody' doesn't exists!\n");
+}
+
+const IMPORT_CSV_HEADER: &str = "name,expire_time,archived_at,created_at,info,contact,telegram,email,notes,referred_by,tags,adjustments,seller,date,money,days,plan";
+
+fn time_from_now(duration: chrono::Duration) -> String {
+    (Utc::now() + duration)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+// one payment of a client as a row of `import` csv, client columns are taken from its first row
+fn payment_row(
+    name: &str,
+    expire_time: &str,
+    info: &str,
+    seller: &str,
+    date: &str,
+    money: u32,
+) -> String {
+    format!("{name},{expire_time},,,{info},,,,,,,,{seller},{date},{money},,")
+}
+
+// seeds clients through `import`, so clients with past payments can be made on every backend
+fn import_clients(context: &TestContext, rows: &[String]) {
+    let path = context.data_path().join("seed.csv");
+    fs::write(&path, format!("{IMPORT_CSV_HEADER}\n{}\n", rows.join("\n"))).unwrap();
+    context
+        .run_command()
+        .args(args!("import --from"))
+        .arg(&path)
+        .assert()
+        .success();
+}
+
+fn import_legacy_client(context: &TestContext) {
+    import_clients(
+        context,
+        &[payment_row(
+            "legacy",
+            "2020-02-01 10:00:00",
+            "old",
+            "pouya",
+            "2020-01-01 10:00:00",
+            60,
+        )],
+    );
+}
+
+fn ledger_with_date_filters(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    import_clients(
+        &context,
+        &[
+            payment_row(
+                "legacy",
+                "2020-02-01 10:00:00",
+                "old",
+                "pouya",
+                "2020-01-01 10:00:00",
+                60,
+            ),
+            payment_row(
+                "legacy",
+                "2020-02-01 10:00:00",
+                "old",
+                "arian",
+                "2020-02-01 10:00:00",
+                55,
+            ),
+            payment_row(
+                "other",
+                "2020-04-01 10:00:00",
+                "idk",
+                "arian",
+                "2020-03-01 12:00:00",
+                50,
+            ),
+        ],
+    );
+
+    context
+        .run_command()
+        .args(args!("ledger --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        2020-01-01 legacy pouya 60 -
+        2020-02-01 legacy arian 55 -
+        2020-03-01 other arian 50 -
+        3 payments   165 0d
+    "});
+    context
+        .run_command()
+        .args(args!("ledger --since 2020-01-15 --until 2020-02-01 --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        2020-02-01 legacy arian 55 -
+        1 payments   55 0d
+    "});
+}
+
+fn removed_clients_are_archived_until_purged(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    import_clients(
+        &context,
+        &[
+            "legacy,2020-02-01 10:00:00,2020-03-01 10:00:00,,old,,,,,,,,pouya,2020-01-01 10:00:00,60,,"
+                .to_string(),
+            payment_row("other", "2020-04-01 10:00:00", "idk", "arian", "2020-03-01 12:00:00", 50),
+        ],
+    );
+
+    context.create_post_script("delete", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("remove --name other"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("");
+
+    let today = Utc::now().format("%Y-%m-%d");
+    context
+        .run_command()
+        .args(args!("list --archived --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(format!(
+            "legacy archived 2020-03-01 pouya(60) old\nother archived {today} arian(50) idk\n"
+        ));
+    context
+        .run_command()
+        .args(args!(
+            "add --name other --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .failure()
+        .stderr("Error: client 'other' is archived, restore or purge it first\n");
+
+    // payments of archived clients still count
+    context
+        .run_command()
+        .args(args!("ledger --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        2020-01-01 legacy pouya 60 -
+        2020-03-01 other arian 50 -
+        2 payments   110 0d
+    "});
+    context
+        .run_command()
+        .args(args!("history --name other --format tsv"))
+        .assert()
+        .success()
+        .stdout("date\tseller\tmoney\tdays\n2020-03-01\tarian\t50\t-\n");
+
+    context
+        .run_command()
+        .args(args!("purge --older-than 365d"))
+        .assert()
+        .success()
+        .stdout("purged legacy\n");
+    context
+        .run_command()
+        .args(args!("restore --name legacy"))
+        .assert()
+        .failure()
+        .stderr("Error: there is no archived client with name 'legacy'\n");
+
+    context.create_post_script("add", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("restore --name other"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("other expired arian(50) idk\n");
+    context
+        .run_command()
+        .args(args!("ledger --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        2020-03-01 other arian 50 -
+        1 payments   50 0d
+    "});
+}
+
+fn create_payments_fixture(context: &TestContext) {
+    import_clients(
+        context,
+        &[
+            payment_row(
+                "legacy",
+                "2020-02-01 10:00:00",
+                "old",
+                "pouya",
+                "2020-01-01 10:00:00",
+                60,
+            ),
+            payment_row(
+                "legacy",
+                "2020-02-01 10:00:00",
+                "old",
+                "pouya",
+                "2020-01-20 10:00:00",
+                20,
+            ),
+            payment_row(
+                "other",
+                "2020-04-01 10:00:00",
+                "idk",
+                "arian",
+                "2020-01-10 12:00:00",
+                40,
+            ),
+            payment_row(
+                "other",
+                "2020-04-01 10:00:00",
+                "idk",
+                "arian",
+                "2020-02-03 10:00:00",
+                55,
+            ),
+        ],
+    );
+}
+
+fn report_revenue(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    create_payments_fixture(&context);
+
+    context
+        .run_command()
+        .args(args!("report revenue --by month"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        2020-01 arian 40  1 40.0
+        2020-01 pouya 80  2 40.0
+        2020-02 arian 55  1 55.0
+        total         175 4 43.8
+    "});
+    context
+        .run_command()
+        .args(args!("report revenue --by day --since 2020-01-15 --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        2020-01-20 pouya 20 1 20.0
+        2020-02-03 arian 55 1 55.0
+        total  75 2 37.5
+    "});
+}
+
+fn plans_fill_payment_terms(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    for plan in [
+        "plan add --name quarterly --days 90 --money 160",
+        "plan add --name monthly --days 30 --money 60",
+    ] {
+        context.run_command().args(args!(plan)).assert().success();
+    }
+    let output = context
+        .run_command()
+        .args(args!("plan list"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let plans: Vec<String> = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .collect();
+    assert_eq!(plans, ["monthly 30 60", "quarterly 90 160"]);
+
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --plan quarterly --seller pouya --info idk"
+        ))
+        .assert()
+        .success();
+    // explicit money overrides the plan
+    context
+        .run_command()
+        .args(args!(
+            "renew --name testcase --plan monthly --money 50 --seller arian --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "add --name other --days 10 --money 20 --seller pouya --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "add --name typo --plan yearly --seller pouya --info idk"
+        ))
+        .assert()
+        .failure()
+        .stderr("Error: plan 'yearly' doesn't exist, add it with 'plan add'\n");
+
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("testcase 119d arian(50) idk\nother 9d pouya(20) idk\n");
+
+    let output = context
+        .run_command()
+        .args(args!("report revenue --group plan --format csv"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let month = Utc::now().format("%Y-%m");
+    assert_eq!(
+        stdout,
+        format!(
+            "period,plan,total,count,average\n{month},-,20,1,20.0\n{month},monthly,50,1,50.0\n{month},quarterly,160,1,160.0\n"
+        )
+    );
+
+    // payments keep the plan after it's removed
+    context
+        .run_command()
+        .args(args!("plan remove --name quarterly"))
+        .assert()
+        .success();
+    let output = context
+        .run_command()
+        .args(args!("list --format json --name-glob testcase"))
+        .assert()
+        .success();
+    let records: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+    assert_eq!(records[0]["payments"][0]["plan"], "quarterly");
+    assert_eq!(records[0]["payments"][1]["days"], 30);
+}
+
+fn report_settle(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    create_payments_fixture(&context);
+
+    context
+        .run_command()
+        .args(args!("report settle --between arian,pouya --month 2020-01"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        arian 40 60 -20
+        pouya 80 60 +20
+        pouya owes arian 20
+    "});
+    context
+        .run_command()
+        .args(args!("report settle --between arian,pouya --month 2020-01 --split 1,3"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        arian 40 30 +10
+        pouya 80 90 -10
+        arian owes pouya 10
+    "});
+    context
+        .run_command()
+        .args(args!("report settle --between arian,pouya --month 2020-03"))
+        .assert()
+        .success()
+        .stdout(indoc! {"
+        arian 0 0 +0
+        pouya 0 0 +0
+        nothing to settle
+    "});
+}
+
+fn list_machine_readable_formats(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    import_clients(
+        &context,
+        &[payment_row(
+            "legacy",
+            "2020-02-01 10:00:00",
+            "\"old, \"\"quoted\"\"\"",
+            "pouya",
+            "2020-01-01 10:00:00",
+            60,
+        )],
+    );
+
+    let output = context
+        .run_command()
+        .args(args!("list --format json"))
+        .assert()
+        .success();
+    let records: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+    assert_eq!(records[0]["name"], "legacy");
+    assert_eq!(records[0]["expire_time"], "2020-02-01 10:00:00");
+    assert_eq!(records[0]["info"], "old, \"quoted\"");
+    assert_eq!(records[0]["payments"][0]["seller"], "pouya");
+    assert_eq!(records[0]["payments"][0]["days"], serde_json::Value::Null);
+    assert!(records[0]["days_left"].as_i64().unwrap() < 0);
+
+    let output = context
+        .run_command()
+        .args(args!("list --format csv"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "name,expire_time,days_left,seller,money,info,payments");
+    assert!(lines[1].starts_with("legacy,2020-02-01 10:00:00,-"));
+    assert!(lines[1].ends_with(",pouya,60,\"old, \"\"quoted\"\"\",2020-01-01 10:00:00 pouya 60 -"));
+}
+
+fn history_tsv_skips_totals(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    import_legacy_client(&context);
+    context
+        .run_command()
+        .args(args!("history --name legacy --format tsv"))
+        .assert()
+        .success()
+        .stdout("date\tseller\tmoney\tdays\n2020-01-01\tpouya\t60\t-\n");
+}
+
+fn list_filters_and_sort(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    for client in [
+        "add --name foo-1 --days 30 --seller pouya --money 60 --info srv1",
+        "add --name foo-2 --days 5 --seller arian --money 40 --info srv2",
+    ] {
+        context.run_command().args(args!(client)).assert().success();
+    }
+    import_clients(
+        &context,
+        &["bar,2020-02-01 10:00:00,,,srv1,,,,,,,,arian,2020-01-01 10:00:00,10,30,".to_string()],
+    );
+
+    let list = |filter: &str, expected: &str| {
+        let output = context
+            .run_command()
+            .args(args!(format!("list --trim-whitespace {filter}")))
+            .assert()
+            .success();
+        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        let names: Vec<&str> = stdout
+            .lines()
+            .map(|line| line.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(names.join(" "), expected, "list {filter}");
+    };
+
+    list("--expired", "bar");
+    list("--expiring-within 7d", "foo-2");
+    list("--expired --expiring-within 7", "foo-2 bar");
+    list("--seller arian", "foo-2 bar");
+    list("--info-contains srv1", "foo-1 bar");
+    list("--name-glob foo-*", "foo-1 foo-2");
+    list("--sort name --limit 2", "bar foo-1");
+    list("--sort total-paid", "foo-1 foo-2 bar");
+    list("--seller arian --sort name --limit 1", "bar");
+}
+
+fn audit_entries(context: &TestContext, filter: &str) -> serde_json::Value {
+    let output = context
+        .run_command()
+        .args(args!(format!("audit --format json {filter}").trim()))
+        .assert()
+        .success();
+    serde_json::from_slice(&output.get_output().stdout).unwrap()
+}
+
+fn audit_records_mutating_commands(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    context
+        .run_command()
+        .env("USER", "tester")
+        .args(args!("add --name testcase --days 30 --seller pouya --money 60 --info idk"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .env_remove("USER")
+        .env_remove("LOGNAME")
+        .args(args!(
+            "add --name other --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("renew --name testcase --days 10 --seller arian --money 30 --info smth"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("edit --name testcase --days 5 --seller pouya --money 1 --info edited"))
+        .assert()
+        .success();
+    context.run_command().arg("list").assert().success();
+
+    let entries = audit_entries(&context, "");
+    let operations: Vec<&str> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["operation"].as_str().unwrap())
+        .collect();
+    assert_eq!(operations, ["add", "add", "renew", "edit"]);
+    assert_eq!(entries[0]["user"], "tester");
+    assert!(entries[1]["user"].as_str().unwrap().starts_with("uid "));
+    // arguments are recorded as given, including --backend
+    let arguments = entries[0]["arguments"].as_array().unwrap();
+    assert!(arguments.iter().any(|argument| argument == "add"));
+    assert_eq!(entries[0]["changes"][0]["before"], serde_json::Value::Null);
+    assert_eq!(entries[0]["changes"][0]["after"]["name"], "testcase");
+
+    // renew also sets info, both should be in same entry
+    let renew = &entries[2]["changes"][0];
+    assert_eq!(renew["before"]["info"], "idk");
+    assert_eq!(renew["after"]["info"], "smth");
+    assert_eq!(renew["after"]["payments"].as_array().unwrap().len(), 2);
+
+    // edit rewrites last payment but the audit keeps what it was
+    let edit = &entries[3]["changes"][0];
+    assert_eq!(edit["before"]["payments"][1]["seller"], "arian");
+    assert_eq!(edit["after"]["payments"][1]["seller"], "pouya");
+
+    let entries = audit_entries(&context, "--name other");
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+    let entries = audit_entries(&context, "--operation renew");
+    assert_eq!(entries.as_array().unwrap().len(), 1);
+}
+
+fn audit_is_rolled_back_with_failed_command(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash\nexit 1");
+    context
+        .run_command()
+        .args(args!("add --name testcase --days 30 --seller pouya --money 60 --info idk"))
+        .assert()
+        .failure();
+
+    let entries = audit_entries(&context, "");
+    assert!(entries.as_array().unwrap().is_empty());
+}
+
+#[test]
+fn audit_log_is_append_only() {
+    let context = TestContext::new();
+    context
+        .run_command()
+        .args(args!("seller add --name sara"))
+        .assert()
+        .success();
+
+    let conn = rusqlite::Connection::open(context.data_path().join("data.db")).unwrap();
+    let error = conn.execute("DELETE FROM audit_log", ()).unwrap_err();
+    assert!(error.to_string().contains("audit log is append-only"));
+}
+
+fn undo_reverts_operations_in_reverse_order(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    context.create_post_script(
+        "rename",
+        "#!/bin/bash\necho \"$@\" > \"$MANJALIOF_DATA/renamed\"",
+    );
+    context.create_post_script(
+        "delete",
+        "#!/bin/bash\necho \"$@\" > \"$MANJALIOF_DATA/deleted\"",
+    );
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "renew --name testcase --days 10 --seller arian --money 30 --info smth"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("rename --old-name testcase --new-name typo"))
+        .assert()
+        .success();
+
+    context.run_command().arg("undo").assert().success();
+    let renamed = fs::read_to_string(context.data_path().join("renamed")).unwrap();
+    assert_eq!(renamed, "typo testcase\n");
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 39d arian(30) smth\n");
+
+    context.run_command().arg("undo").assert().success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 29d pouya(60) idk\n");
+
+    context.run_command().arg("undo").assert().success();
+    let deleted = fs::read_to_string(context.data_path().join("deleted")).unwrap();
+    assert_eq!(deleted, "testcase\n");
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("");
+
+    context
+        .run_command()
+        .arg("undo")
+        .assert()
+        .failure()
+        .stderr("Error: there is no operation to undo\n");
+
+    let entries = audit_entries(&context, "--operation undo");
+    let reverts: Vec<u64> = entries
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["reverts"].as_u64().unwrap())
+        .collect();
+    assert_eq!(reverts, [3, 2, 1]);
+}
+
+fn undo_refuses_when_client_changed_later(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "add --name other --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("set-info --name testcase --info smth"))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!("undo --id 1"))
+        .assert()
+        .failure()
+        .stderr("Error: cannot undo operation 1: client 'testcase' is changed later by operation 3 (set-info)\n");
+
+    // other client isn't touched by later operations
+    context
+        .run_command()
+        .args(args!("--skip-post-script undo --id 2"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("undo --id 2"))
+        .assert()
+        .failure()
+        .stderr("Error: operation 2 is already undone\n");
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("testcase 29d pouya(60) smth\n");
+}
+
+fn create_expired_clients(context: &TestContext) {
+    let rows: Vec<String> = [("old", -20), ("bad", -12), ("recent", -2)]
+        .into_iter()
+        .map(|(name, days)| {
+            let expire_time = time_from_now(chrono::Duration::days(days));
+            payment_row(
+                name,
+                &expire_time,
+                "idk",
+                "pouya",
+                "2020-01-01 10:00:00",
+                60,
+            )
+        })
+        .collect();
+    import_clients(context, &rows);
+    context.create_post_script(
+        "delete",
+        "#!/bin/bash\nif [ \"$1\" = bad ]; then echo nope >&2; exit 1; fi\necho \"$1\" >> \"$MANJALIOF_DATA/deleted\"",
+    );
+}
+
+fn cleanup_dry_run_and_confirmation(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    create_expired_clients(&context);
+    context.create_post_script(
+        "pre-cleanup",
+        "#!/bin/bash\necho \"$@\" >> \"$MANJALIOF_DATA/pre-cleanup\"",
+    );
+
+    let output = context
+        .run_command()
+        .args(args!("cleanup --dry-run"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.pop(), Some("dry run, nothing is saved"));
+    let names: Vec<&str> = lines
+        .iter()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(names, ["old", "bad"]);
+    assert!(lines.iter().all(|line| line.contains(" -> archived ")));
+    assert!(!context.data_path().join("pre-cleanup").exists());
+    assert!(!context.data_path().join("deleted").exists());
+    context
+        .run_command()
+        .args(args!("list --archived"))
+        .assert()
+        .success()
+        .stdout("");
+
+    context
+        .run_command()
+        .arg("cleanup")
+        .assert()
+        .failure()
+        .stderr("Error: cannot ask for confirmation without a terminal, pass --yes\n");
+
+    // without --keep-going it stops at failing script, clients whose script ran stay archived
+    context
+        .run_command()
+        .args(args!("cleanup --yes"))
+        .assert()
+        .failure()
+        .stderr("[delete] nope\nError: cleanup stopped at 'bad', already archived: old, pass --keep-going to skip failed clients: post script exited due to a failure: nope\n");
+    let deleted = fs::read_to_string(context.data_path().join("deleted")).unwrap();
+    assert_eq!(deleted, "old\n");
+    let today = Utc::now().format("%Y-%m-%d");
+    context
+        .run_command()
+        .args(args!("list --archived --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(format!("old archived {today} pouya(60) idk\n"));
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("recent expired pouya(60) idk\nbad expired pouya(60) idk\n");
+    let entries = audit_entries(&context, "");
+    // the first entry is the import that made clients
+    assert_eq!(entries[1]["operation"], "cleanup");
+    assert_eq!(entries[1]["changes"].as_array().unwrap().len(), 1);
+}
+
+fn cleanup_keep_going_with_configured_grace(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    create_expired_clients(&context);
+    fs::write(
+        context.data_path().join("config.toml"),
+        "[cleanup]\ngrace_days = 15\n",
+    )
+    .unwrap();
+
+    context
+        .run_command()
+        .args(args!("cleanup --yes --keep-going"))
+        .assert()
+        .success();
+    let today = Utc::now().format("%Y-%m-%d");
+    context
+        .run_command()
+        .args(args!("list --archived --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout(format!("old archived {today} pouya(60) idk\n"));
+
+    let output = context
+        .run_command()
+        .args(args!("cleanup --yes --keep-going --grace 10d"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("bad"));
+    assert!(lines[0].ends_with("failed: post script exited due to a failure: nope"));
+    assert_eq!(
+        lines[1],
+        "1 of 1 clients are kept because their post script failed"
+    );
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("recent expired pouya(60) idk\nbad expired pouya(60) idk\n");
+}
+
+fn config_controls_defaults_and_limits(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    let config_path = context.data_path().join("custom.toml");
+    fs::write(
+        &config_path,
+        indoc! {"
+            [display]
+            warn_days = 40
+
+            [limits]
+            max_name_length = 5
+        "},
+    )
+    .unwrap();
+    let config_arg = format!("--config {}", config_path.to_str().unwrap());
+
+    let output = context
+        .run_command()
+        .args(args!(format!("{config_arg} config show")))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("warn_days = 40\n"));
+    assert!(stdout.contains("days = 30\n"));
+    context
+        .run_command()
+        .args(args!(format!("{config_arg} config validate")))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(format!(
+            "{config_arg} add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        )))
+        .assert()
+        .failure()
+        .stderr("Error: cannot validate name: text is too short or too long\n");
+    context
+        .run_command()
+        .args(args!(format!(
+            "{config_arg} add --name test --days 30 --seller pouya --money 60 --info idk"
+        )))
+        .assert()
+        .success();
+
+    // 29 days left is highlighted with warn_days = 40
+    let output = context
+        .run_command()
+        .args(args!(format!("{config_arg} list --trim-whitespace")))
+        .env("CLICOLOR_FORCE", "1")
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("\u{1b}[33m29d"), "{stdout:?}");
+
+    fs::write(
+        context.data_path().join("config.toml"),
+        "[defaults]\ndays = 0\n",
+    )
+    .unwrap();
+    context
+        .run_command()
+        .args(args!("config validate"))
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: invalid config '{}': defaults.days must be more than 0\n",
+            context.data_path().join("config.toml").to_str().unwrap()
+        ));
+    context
+        .run_command()
+        .args(args!("--config /nonexistent.toml list"))
+        .assert()
+        .failure()
+        .stderr("Error: config file '/nonexistent.toml' doesn't exist\n");
+}
+
+fn hooks_get_payload_and_pre_hooks_veto(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script(
+        "edit.d/10-payload",
+        "#!/bin/bash\ncat > \"$MANJALIOF_DATA/payload\"",
+    );
+    context.create_post_script(
+        "edit.d/20-env",
+        "#!/bin/bash\necho \"$MANJALIOF_OP $MANJALIOF_PHASE $MANJALIOF_CLIENT $@\" > \"$MANJALIOF_DATA/env\"",
+    );
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!(
+            "edit --name testcase --days 20 --seller arian --money 50 --info smth"
+        ))
+        .assert()
+        .success();
+    let env = fs::read_to_string(context.data_path().join("env")).unwrap();
+    assert_eq!(env, "edit post testcase testcase\n");
+    let payload: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(context.data_path().join("payload")).unwrap())
+            .unwrap();
+    assert_eq!(payload["operation"], "edit");
+    let change = &payload["changes"][0];
+    assert_eq!(change["client"], "testcase");
+    assert_eq!(change["before"]["info"], "idk");
+    assert_eq!(change["after"]["info"], "smth");
+    assert_eq!(change["payment"]["money"], 50);
+    assert_eq!(change["seller"], "arian");
+
+    context.create_post_script(
+        "pre-set-info",
+        "#!/bin/bash\necho \"info is frozen\" >&2\nexit 1",
+    );
+    context
+        .run_command()
+        .args(args!("set-info --name testcase --info new"))
+        .assert()
+        .failure()
+        .stderr("[pre-set-info] info is frozen\nError: operation is vetoed by 'pre-set-info': info is frozen\n");
+    context
+        .run_command()
+        .args(args!(
+            "--skip-post-script set-info --name testcase --info new"
+        ))
+        .assert()
+        .failure();
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("testcase 19d arian(50) smth\n");
+}
+
+fn failed_hooks_are_queued_and_retried(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script(
+        "renew",
+        "#!/bin/bash\n[ -f \"$MANJALIOF_DATA/online\" ] || { echo \"server is down\" >&2; exit 1; }",
+    );
+    context.create_post_script("rename", "#!/bin/bash\nexec sleep 5");
+    fs::write(
+        context.data_path().join("config.toml"),
+        indoc! {"
+            [hooks]
+            retries = 1
+            retry_delay_secs = 0
+            queue_failed = true
+
+            [hooks.timeouts]
+            rename = 1
+        "},
+    )
+    .unwrap();
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!(
+            "renew --name testcase --days 10 --seller arian --money 30 --info smth"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("rename --old-name testcase --new-name other"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .success()
+        .stdout("other 39d arian(30) smth\n");
+
+    let output = context
+        .run_command()
+        .args(args!("hooks list"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("post script exited due to a failure: server is down"));
+    assert!(stdout.contains("'rename' is killed after running for 1s"));
+
+    fs::write(context.data_path().join("online"), "").unwrap();
+    context
+        .run_command()
+        .args(args!("hooks retry --id 1"))
+        .assert()
+        .success();
+    let output = context
+        .run_command()
+        .args(args!("hooks list"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(!stdout.contains("server is down"));
+    assert!(stdout.contains("rename"));
+
+    // id of the last hook isn't given again after it's retried
+    context.create_post_script("rename", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!("hooks retry --id 2"))
+        .assert()
+        .success();
+    fs::remove_file(context.data_path().join("online")).unwrap();
+    context
+        .run_command()
+        .args(args!(
+            "renew --name other --days 10 --seller arian --money 30 --info smth"
+        ))
+        .assert()
+        .success();
+    let output = context
+        .run_command()
+        .args(args!("hooks list"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(stdout.starts_with("3 "));
+}
+
+fn hook_output_is_streamed_and_logged(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script(
+        "add",
+        "#!/bin/bash\necho \"provisioning $1\"\necho \"slow server\" >&2",
+    );
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success()
+        .stdout("[add] provisioning testcase\n")
+        .stderr("[add] slow server\n");
+
+    let output = context
+        .run_command()
+        .args(args!("hooks log --script add"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("-add.log\n# add testcase\n# operation: add, client: testcase\n"));
+    assert!(stdout.contains("out| provisioning testcase\n"));
+    assert!(stdout.contains("err| slow server\n"));
+    assert!(stdout.ends_with("# exit status: 0\n"));
+    context
+        .run_command()
+        .args(args!("hooks log --script renew"))
+        .assert()
+        .success()
+        .stdout("");
+
+    fs::write(
+        context.data_path().join("config.toml"),
+        indoc! {"
+            [hooks]
+            keep_logs = 2
+        "},
+    )
+    .unwrap();
+    context.create_post_script("renew", "#!/bin/bash");
+    for days in [10, 20, 30] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "renew --name testcase --days {days} --seller pouya --money 60 --info idk"
+            )))
+            .assert()
+            .success();
+    }
+    let logs: Vec<String> = fs::read_dir(context.data_path().join("logs"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+    assert_eq!(logs.len(), 2);
+    assert!(logs.iter().all(|name| name.ends_with("-renew.log")));
+}
+
+fn notify_sends_once_per_threshold(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    let rows: Vec<String> = [
+        ("expired", chrono::Duration::days(-1)),
+        (
+            "soon",
+            chrono::Duration::days(2) + chrono::Duration::hours(1),
+        ),
+        ("later", chrono::Duration::days(20)),
+    ]
+    .into_iter()
+    .map(|(name, duration)| {
+        payment_row(
+            name,
+            &time_from_now(duration),
+            "idk",
+            "pouya",
+            "2020-01-01 10:00:00",
+            60,
+        )
+    })
+    .collect();
+    import_clients(&context, &rows);
+
+    context
+        .run_command()
+        .args(args!("notify --dry-run"))
+        .assert()
+        .success();
+    assert!(!context.data_path().join("notifications").exists());
+    context
+        .run_command()
+        .args(args!("--skip-post-script notify"))
+        .assert()
+        .success();
+    assert!(!context.data_path().join("notifications").exists());
+    context.run_command().arg("notify").assert().success();
+    let spool = fs::read_to_string(context.data_path().join("notifications")).unwrap();
+    let lines: Vec<Vec<&str>> = spool
+        .lines()
+        .map(|line| line.split('\t').skip(1).take(2).collect())
+        .collect();
+    assert_eq!(lines, vec![vec!["expired", "expired"], vec!["soon", "3d"]]);
+    context
+        .run_command()
+        .arg("notify")
+        .assert()
+        .success()
+        .stdout("nothing to notify\n");
+
+    context.create_post_script(
+        "notify",
+        "#!/bin/bash\necho \"$@\" >> \"$MANJALIOF_DATA/notified\"",
+    );
+    context
+        .run_command()
+        .args(args!(
+            "add --name new --days 1 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("--skip-post-script notify"))
+        .assert()
+        .success();
+    assert!(!context.data_path().join("notified").exists());
+    context.run_command().arg("notify").assert().success();
+    let notified = fs::read_to_string(context.data_path().join("notified")).unwrap();
+    assert_eq!(notified, "new 1d\n");
+}
+
+fn client_profile_is_stored_and_searchable(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    let notes = "x".repeat(100);
+    context
+        .run_command()
+        .args(args!(format!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk --telegram @Test --notes {notes}"
+        )))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "add --name other --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!(
+            "edit --name testcase --days 20 --seller arian --money 50 --info smth --email nope"
+        ))
+        .assert()
+        .failure()
+        .stderr("Error: cannot validate email: 'nope' is not an email address\n");
+    context
+        .run_command()
+        .args(args!(
+            "edit --name testcase --days 20 --seller arian --money 50 --info smth --email a@b.io --referred-by other"
+        ))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!("list --search @test --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("testcase 19d arian(50) smth\n");
+    let output = context
+        .run_command()
+        .args(args!("list --search A@B --format json"))
+        .assert()
+        .success();
+    let records: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+    let record = &records[0];
+    assert_eq!(record["name"], "testcase");
+    assert_eq!(record["telegram"], "@Test");
+    assert_eq!(record["email"], "a@b.io");
+    assert_eq!(record["referred_by"], "other");
+    assert_eq!(record["notes"], notes);
+    assert!(record["contact"].is_null());
+    assert!(record["created_at"].is_string());
+
+    let output = context
+        .run_command()
+        .args(args!("list -v --search other"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("@Test a@b.io"));
+}
+
+fn tags_target_bulk_operations(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    context.create_post_script(
+        "delete",
+        "#!/bin/bash\necho \"$@\" >> \"$MANJALIOF_DATA/deleted\"",
+    );
+    for name in ["one", "two", "three"] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "add --name {name} --days 30 --seller pouya --money 60 --info idk"
+            )))
+            .assert()
+            .success();
+    }
+
+    context
+        .run_command()
+        .args(args!("tag add --name one vip server_2"))
+        .assert()
+        .failure()
+        .stderr(
+            "Error: cannot validate tag 'server_2': only ascii alphanumeric values are valid\n",
+        );
+    context
+        .run_command()
+        .args(args!("tag add --name one vip server-2"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("tag add --name two vip"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("tag add --name nobody vip"))
+        .assert()
+        .failure()
+        .stderr("Error: client with name 'nobody' doesn't exists!\n");
+
+    context
+        .run_command()
+        .args(args!("list --tag vip --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 29d pouya(60) idk\ntwo 29d pouya(60) idk\n");
+    context
+        .run_command()
+        .args(args!("tag list"))
+        .assert()
+        .success()
+        .stdout("server-2 1\nvip      2\n");
+
+    context
+        .run_command()
+        .args(args!("set-info --tag vip --info special --yes"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("renew-all --tag vip --days 10 --yes"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("list --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 39d pouya(60) special\nthree 29d pouya(60) idk\ntwo 39d pouya(60) special\n");
+
+    context
+        .run_command()
+        .args(args!("tag remove --name two vip"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("remove --tag vip --yes"))
+        .assert()
+        .success();
+    let deleted = fs::read_to_string(context.data_path().join("deleted")).unwrap();
+    assert_eq!(deleted, "one\n");
+    context
+        .run_command()
+        .args(args!("remove --tag vip --yes"))
+        .assert()
+        .failure()
+        .stderr("Error: no client matches the target\n");
+    context
+        .run_command()
+        .args(args!("list --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("three 29d pouya(60) idk\ntwo 39d pouya(60) special\n");
+}
+
+fn bulk_edit_compensates_targeted_clients(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    for (name, seller) in [("one", "pouya"), ("two", "pouya"), ("three", "arian")] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "add --name {name} --days 30 --seller {seller} --money 60 --info idk"
+            )))
+            .assert()
+            .success();
+    }
+
+    context
+        .run_command()
+        .args(args!("bulk-edit --seller pouya --days 3"))
+        .assert()
+        .failure()
+        .stderr("Error: cannot ask for confirmation without a terminal, pass --yes\n");
+    context
+        .run_command()
+        .args(args!("bulk-edit --seller pouya --yes"))
+        .assert()
+        .failure()
+        .stderr("Error: nothing to edit, pass --days, --info, --add-tag or --remove-tag\n");
+    context
+        .run_command()
+        .args(args!("bulk-edit --seller nobody --days 3 --yes"))
+        .assert()
+        .failure()
+        .stderr("Error: no client matches the target\n");
+    context
+        .run_command()
+        .args(args!(
+            "bulk-edit --seller pouya --days 3 --add-tag down --info compensated --yes"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("bulk-edit --names two,three --days -1 --yes"))
+        .assert()
+        .success();
+
+    context
+        .run_command()
+        .args(args!("list --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 32d pouya(60) compensated\nthree 28d arian(60) idk\ntwo 31d pouya(60) compensated\n");
+    context
+        .run_command()
+        .args(args!("list --tag down --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 32d pouya(60) compensated\ntwo 31d pouya(60) compensated\n");
+
+    context.run_command().arg("undo").assert().success();
+    context.run_command().arg("undo").assert().success();
+    context
+        .run_command()
+        .args(args!("list --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 29d pouya(60) idk\nthree 29d arian(60) idk\ntwo 29d pouya(60) idk\n");
+}
+
+fn adjustments_are_shown_in_history_but_not_paid(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .env("USER", "tester")
+        .args(args!("renew-all --days 3 --reason downtime --yes"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .env("USER", "tester")
+        .args(args!("bulk-edit --names testcase --days -1 --yes"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .env("USER", "tester")
+        .args(args!(
+            "edit --name testcase --days 40 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+
+    let today = Utc::now().format("%Y-%m-%d");
+    let output = context
+        .run_command()
+        .args(args!("history --name testcase --format tsv"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert_eq!(
+        stdout,
+        format!(
+            "date\tseller\tmoney\tdays\treason\n\
+            {today}\tpouya\t60\t30d\t\n\
+            {today}\ttester\t-\t+3d\tdowntime\n\
+            {today}\ttester\t-\t-1d\tbulk-edit\n\
+            {today}\ttester\t-\t+8d\tedit\n"
+        )
+    );
+    let output = context
+        .run_command()
+        .args(args!("history --name testcase --trim-whitespace"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert_eq!(
+        stdout.lines().last().unwrap(),
+        "1 payments  60 30d +10d adjusted"
+    );
+}
+
+#[test]
+fn backend_is_chosen_by_config_or_argument() {
+    let context = TestContext::new();
+    context.create_post_script("add", "#!/bin/bash");
+    fs::write(
+        context.data_path().join("config.toml"),
+        "[database]\nbackend = \"json\"\n",
+    )
+    .unwrap();
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+
+    let data = fs::read_to_string(context.data_path().join("data.json")).unwrap();
+    assert!(data.contains("\"name\": \"testcase\""));
+    assert!(!context.data_path().join("data.db").exists());
+    context
+        .run_command()
+        .args(args!("db migrate"))
+        .assert()
+        .failure()
+        .stderr("Error: db commands are only available with sqlite backend\n");
+    context
+        .run_command()
+        .args(args!("--backend sqlite list"))
+        .assert()
+        .success()
+        .stdout("");
+}
+
+fn export_and_import_between_formats(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script("renew", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!(
+            "add --name one --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "renew --name one --days 30 --seller pouya --money 50 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "add --name two --days 10 --seller pouya --money 20 --info a,\"b\""
+        ))
+        .assert()
+        .success();
+
+    let export_dir = TestContext::new();
+    for file in ["dump.json", "dump.csv", "dump.db"] {
+        let path = export_dir.data_path().join(file);
+        context
+            .run_command()
+            .args(args!("export --out"))
+            .arg(&path)
+            .assert()
+            .success()
+            .stdout(format!(
+                "exported 2 clients to '{}'\n",
+                path.to_str().unwrap()
+            ));
+        context
+            .run_command()
+            .args(args!("export --out"))
+            .arg(&path)
+            .assert()
+            .failure()
+            .stderr(format!(
+                "Error: '{}' already exists, won't overwrite it\n",
+                path.to_str().unwrap()
+            ));
+
+        let other = TestContext::with_backend(backend);
+        other
+            .run_command()
+            .args(args!("import --from"))
+            .arg(&path)
+            .assert()
+            .success()
+            .stdout("imported 2 clients, 0 of them already existed\n");
+        other
+            .run_command()
+            .args(args!("list --sort name --trim-whitespace"))
+            .assert()
+            .success()
+            .stdout("one 59d pouya(50) idk\ntwo 9d pouya(20) a,\"b\"\n");
+    }
+
+    let path = export_dir.data_path().join("dump.csv");
+    context
+        .run_command()
+        .args(args!("import --from"))
+        .arg(&path)
+        .assert()
+        .failure()
+        .stderr("Error: clients already exist: one, two, pass --merge or --replace\n");
+    context
+        .run_command()
+        .args(args!("import --merge --replace --from"))
+        .arg(&path)
+        .assert()
+        .failure()
+        .stderr("Error: --merge and --replace conflicts with each other\n");
+
+    context
+        .run_command()
+        .args(args!(
+            "renew --name two --days 30 --seller pouya --money 40 --info a,\"b\""
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("import --merge --from"))
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("imported 2 clients, 2 of them already existed\n");
+    context
+        .run_command()
+        .args(args!("list --name-glob two --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("two 39d pouya(40) a,\"b\"\n");
+    context
+        .run_command()
+        .args(args!("import --replace --from"))
+        .arg(&path)
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("list --name-glob two --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("two 9d pouya(20) a,\"b\"\n");
+
+    let invalid = export_dir.data_path().join("invalid.csv");
+    let content = fs::read_to_string(&path).unwrap().replace("one,", "o ne,");
+    fs::write(&invalid, content).unwrap();
+    let output = context
+        .run_command()
+        .args(args!("import --replace --from"))
+        .arg(&invalid)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("Error: cannot import client 'o ne': "));
+    context
+        .run_command()
+        .args(args!("list --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 59d pouya(50) idk\ntwo 9d pouya(20) a,\"b\"\n");
+
+    context
+        .run_command()
+        .args(args!("bulk-edit --names one --days 3 --yes"))
+        .assert()
+        .success();
+    let lock_path = export_dir.data_path().join("adjusted.json.lock");
+    for file in ["adjusted.csv", "adjusted.json"] {
+        let path = export_dir.data_path().join(file);
+        context
+            .run_command()
+            .args(args!("export --out"))
+            .arg(&path)
+            .assert()
+            .success();
+        // source of import is only read, so a process using it doesn't block the import
+        if file.ends_with(".json") {
+            fs::write(&lock_path, "1").unwrap();
+        }
+        let other = TestContext::with_backend(backend);
+        other
+            .run_command()
+            .args(args!("import --from"))
+            .arg(&path)
+            .assert()
+            .success();
+        let output = other
+            .run_command()
+            .args(args!("history --name one --trim-whitespace"))
+            .assert()
+            .success();
+        let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+        assert_eq!(
+            stdout.lines().last().unwrap(),
+            "2 payments  110 60d +3d adjusted"
+        );
+    }
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), "1");
+
+    // payments keep sellers that are removed since then
+    context
+        .run_command()
+        .args(args!("seller add --name sara"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!(
+            "add --name three --days 10 --seller sara --money 20 --info idk"
+        ))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("seller remove --name sara"))
+        .assert()
+        .success();
+    for file in ["removed.json", "removed.csv", "removed.db"] {
+        let path = export_dir.data_path().join(file);
+        context
+            .run_command()
+            .args(args!("export --out"))
+            .arg(&path)
+            .assert()
+            .success();
+        let other = TestContext::with_backend(backend);
+        other
+            .run_command()
+            .args(args!("import --from"))
+            .arg(&path)
+            .assert()
+            .success()
+            .stdout("imported 3 clients, 0 of them already existed\n");
+        other
+            .run_command()
+            .args(args!("seller list"))
+            .assert()
+            .success()
+            .stdout("arian\npouya\n");
+    }
+}
+
+#[test]
+fn json_commits_are_locked_and_checked() {
+    let context = TestContext::with_backend(Backend::Json);
+    context.create_post_script("add", "#!/bin/bash");
+    context
+        .run_command()
+        .args(args!(
+            "add --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    let mut files: Vec<String> = fs::read_dir(context.data_path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("data.json"))
+        .collect();
+    files.sort();
+    assert_eq!(files, ["data.json"]);
+
+    let lock_path = context.data_path().join("data.json.lock");
+    fs::write(&lock_path, "4242").unwrap();
+    context
+        .run_command()
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: database is locked by another process (pid 4242), remove '{}' if it isn't running\n",
+            lock_path.to_str().unwrap()
+        ));
+    fs::remove_file(&lock_path).unwrap();
+
+    // a pre hook runs before commit, like another program editing the file meanwhile
+    context.create_post_script(
+        "pre-renew",
+        "#!/bin/bash\necho >> \"$MANJALIOF_DATA/data.json\"",
+    );
+    context.create_post_script("renew", "#!/bin/bash");
+    let data_path = context.data_path().join("data.json");
+    context
+        .run_command()
+        .args(args!(
+            "renew --name testcase --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: CRITICAL ERROR: cannot commit changes: '{}' was modified by another program since it was opened, nothing is saved\n",
+            data_path.to_str().unwrap()
+        ));
+    assert!(!lock_path.exists());
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("testcase 29d pouya(60) idk\n");
+}
+
+fn backup_ids(context: &TestContext) -> Vec<String> {
+    let output = context
+        .run_command()
+        .args(args!("backup list"))
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split_whitespace().next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn backups_are_rotated_and_restored() {
+    let context = TestContext::new();
+    context.create_post_script("add", "#!/bin/bash");
+    fs::write(
+        context.data_path().join("config.toml"),
+        "[backup]\nkeep = 3\n",
+    )
+    .unwrap();
+    for name in ["one", "two"] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "add --name {name} --days 30 --seller pouya --money 60 --info idk"
+            )))
+            .assert()
+            .success();
+    }
+    // first add creates database, so only the second one is backed up
+    assert_eq!(backup_ids(&context).len(), 1);
+
+    context
+        .run_command()
+        .args(args!("set-info --all --info oops --yes"))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 29d pouya(60) oops\ntwo 29d pouya(60) oops\n");
+    let ids = backup_ids(&context);
+    assert_eq!(ids.len(), 2);
+    let listed = context
+        .run_command()
+        .args(args!("backup list"))
+        .output()
+        .unwrap();
+    let listed = String::from_utf8(listed.stdout).unwrap();
+    assert!(listed.lines().nth(1).unwrap().contains(" set-info "));
+
+    context
+        .run_command()
+        .args(args!(format!("backup restore {}", ids[1])))
+        .assert()
+        .failure()
+        .stderr("Error: cannot ask for confirmation without a terminal, pass --yes\n");
+    context
+        .run_command()
+        .args(args!("backup restore 20000101-000000.000 --yes"))
+        .assert()
+        .failure()
+        .stderr("Error: backup '20000101-000000.000' doesn't exist\n");
+    context
+        .run_command()
+        .args(args!(format!("backup restore {} --yes", ids[1])))
+        .assert()
+        .success();
+    context
+        .run_command()
+        .args(args!("list --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 29d pouya(60) idk\ntwo 29d pouya(60) idk\n");
+
+    // the state before restoring is kept and only the newest 3 remain
+    let ids_after_restore = backup_ids(&context);
+    assert_eq!(ids_after_restore.len(), 3);
+    assert_eq!(ids_after_restore[..2], ids[..]);
+
+    let damaged_path = context
+        .data_path()
+        .join("backups")
+        .join(format!("{}_restore.db", ids_after_restore[2]));
+    fs::write(damaged_path, "not a database").unwrap();
+    context
+        .run_command()
+        .args(args!(format!(
+            "backup restore {} --yes",
+            ids_after_restore[2]
+        )))
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: backup '{}' is damaged: file is not a database\n",
+            ids_after_restore[2]
+        ));
+}
+
+#[test]
+fn backup_schedule_is_configurable() {
+    let context = TestContext::new();
+    context.create_post_script("add", "#!/bin/bash");
+    fs::write(
+        context.data_path().join("config.toml"),
+        "[backup]\nschedule = \"daily\"\n",
+    )
+    .unwrap();
+    for name in ["one", "two", "three"] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "add --name {name} --days 30 --seller pouya --money 60 --info idk"
+            )))
+            .assert()
+            .success();
+    }
+    assert_eq!(backup_ids(&context).len(), 1);
+    context
+        .run_command()
+        .args(args!("backup create"))
+        .assert()
+        .success();
+    assert_eq!(backup_ids(&context).len(), 2);
+
+    fs::write(
+        context.data_path().join("config.toml"),
+        "[backup]\nschedule = \"never\"\n",
+    )
+    .unwrap();
+    context
+        .run_command()
+        .args(args!(
+            "add --name four --days 30 --seller pouya --money 60 --info idk"
+        ))
+        .assert()
+        .success();
+    assert_eq!(backup_ids(&context).len(), 2);
+
+    context
+        .run_command()
+        .args(args!("--backend json backup list"))
+        .assert()
+        .failure()
+        .stderr("Error: backup commands are only available with sqlite backend\n");
+}
+
+fn bulk_operations_preview_and_dry_run(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    context.create_post_script(
+        "renew-all",
+        "#!/bin/bash\necho \"$@\" >> \"$MANJALIOF_DATA/renewed\"",
+    );
+    for name in ["one", "two"] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "add --name {name} --days 30 --seller pouya --money 60 --info idk"
+            )))
+            .assert()
+            .success();
+    }
+    let dry_run_lines = |arguments: &str| -> Vec<String> {
+        let output = context
+            .run_command()
+            .args(args!(arguments))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let mut lines: Vec<String> = stdout.lines().map(str::to_string).collect();
+        assert_eq!(lines.pop().unwrap(), "dry run, nothing is saved");
+        lines
+    };
+    let expire_after = |days: i64| {
+        (Utc::now() + chrono::Duration::days(days))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+
+    context.create_post_script(
+        "pre-set-info",
+        "#!/bin/bash\necho \"$@\" >> \"$MANJALIOF_DATA/pre-set-info\"",
+    );
+    let lines = dry_run_lines("set-info --all --info oops --dry-run");
+    assert!(!context.data_path().join("pre-set-info").exists());
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("one "));
+    assert!(lines.iter().all(|line| line.contains(" idk -> oops ")));
+
+    let lines = dry_run_lines("renew-all --days 10 --dry-run");
+    assert_eq!(
+        lines[0],
+        "you are renewing all clients that are not expired!"
+    );
+    let renewed = format!("{} -> {}", expire_after(30), expire_after(40));
+    assert!(lines[1..].iter().all(|line| line.contains(&renewed)));
+    assert!(!context.data_path().join("renewed").exists());
+
+    let lines = dry_run_lines("bulk-edit --names two --days -5 --add-tag vip --dry-run");
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].starts_with("two "));
+    assert!(lines[0].contains(&format!("{} -> {}", expire_after(30), expire_after(25))));
+    assert!(lines[0].trim_end().ends_with(" -> vip"));
+
+    let lines = dry_run_lines("remove --names one --dry-run");
+    assert!(lines[0].contains(&format!("{} -> archived", expire_after(30))));
+
+    // one client isn't confirmed, the dry run still previews it
+    let lines = dry_run_lines("remove --name one --dry-run");
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains(&format!("{} -> archived", expire_after(30))));
+    let lines = dry_run_lines("set-info --name two --info oops --dry-run");
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].starts_with("two "));
+    assert!(lines[0].contains(" idk -> oops "));
+
+    // refusing the preview rolls back what is already applied
+    context
+        .run_command()
+        .args(args!("set-info --all --info oops"))
+        .assert()
+        .failure()
+        .stderr("Error: cannot ask for confirmation without a terminal, pass --yes\n");
+    context
+        .run_command()
+        .args(args!("list --sort name --trim-whitespace"))
+        .assert()
+        .success()
+        .stdout("one 29d pouya(60) idk\ntwo 29d pouya(60) idk\n");
+    let entries = audit_entries(&context, "");
+    assert_eq!(entries.as_array().unwrap().len(), 2);
+    context
+        .run_command()
+        .args(args!("renew-all --days 10 --yes"))
+        .assert()
+        .success();
+    let renewed = fs::read_to_string(context.data_path().join("renewed")).unwrap();
+    assert_eq!(renewed, "one two\n");
+}
+
+fn glob_classes_and_search_case_folding(backend: Backend) {
+    let context = TestContext::with_backend(backend);
+    context.create_post_script("add", "#!/bin/bash");
+    for (name, info) in [("ab1", "Ärger"), ("ac1", "Idk"), ("a-1", "idk")] {
+        context
+            .run_command()
+            .args(args!(format!(
+                "add --name {name} --days 30 --seller pouya --money 60 --info {info}"
+            )))
+            .assert()
+            .success();
+    }
+    let listed_names = |arguments: &str| -> String {
+        let output = context
+            .run_command()
+            .args(args!(format!(
+                "list --sort name --trim-whitespace {arguments}"
+            )))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| line.split(' ').next().unwrap())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    };
+
+    assert_eq!(listed_names("--name-glob a[b]1"), "ab1");
+    assert_eq!(listed_names("--name-glob a[^b]1"), "a-1 ac1");
+    assert_eq!(listed_names("--name-glob a[a-c-]1"), "a-1 ab1 ac1");
+    assert_eq!(listed_names("--name-glob a[b"), "");
+    // only ascii letters are folded, like sqlite lower()
+    assert_eq!(listed_names("--search IDK"), "a-1 ac1");
+    assert_eq!(listed_names("--search ärger"), "");
+    assert_eq!(listed_names("--search Ärger"), "ab1");
+}